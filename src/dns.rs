@@ -3,7 +3,20 @@ pub mod dns_question;
 pub mod dns_header;
 pub mod query_type;
 pub mod result_code;
+pub mod opcode;
 pub mod dns_record;
 pub mod dns_packet;
+pub mod dns_packet_view;
+pub mod packet_builder;
+pub mod edns;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod name;
+pub mod reverse;
+pub mod srv;
 pub(crate) mod byte_packet_buffer_error;
-pub(crate) mod query_class;
\ No newline at end of file
+pub(crate) mod query_class;
+pub(crate) mod presentation;
+pub(crate) mod presentation_error;
+pub(crate) mod parse_error;
+pub(crate) mod build_error;
@@ -0,0 +1,37 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use crate::dns::parse_error::Section;
+use crate::dns::query_type::QueryType;
+use crate::dns::result_code::ResultCode;
+
+/// A response which breaks the rules on what belongs in each of its sections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// An OPT pseudo-record was added as a regular record. The EDNS
+    /// information of a message is set on its own instead, and always written
+    /// as the last record of the additional section.
+    OptRecord(Section),
+    /// The answer section holds records while there is no question they could
+    /// be answering.
+    AnswerWithoutQuestion,
+    /// The authority section holds a record other than the NS records of a
+    /// referral or the SOA record of a negative answer.
+    InvalidAuthorityRecord(QueryType),
+    /// The response code does not fit within the header and needs the OPT
+    /// pseudo-record to carry its upper bits, but the response has no EDNS
+    /// information.
+    ExtendedRcodeWithoutEdns(ResultCode),
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::OptRecord(section) => write!(f, "OPT pseudo-record added to the {}", section),
+            BuildError::AnswerWithoutQuestion => write!(f, "answer section is not empty while there is no question"),
+            BuildError::InvalidAuthorityRecord(qtype) => write!(f, "{} record added to the authority section", qtype),
+            BuildError::ExtendedRcodeWithoutEdns(rcode) => write!(f, "response code {} requires EDNS", rcode),
+        }
+    }
+}
+
+impl Error for BuildError {}
@@ -1,45 +1,158 @@
+use std::collections::HashMap;
 use crate::dns::byte_packet_buffer_error::BytePacketBufferError;
-use crate::dns::byte_packet_buffer_error::BytePacketBufferError::{EndOfBuffer, ExceededJumpCount, QueryDomainNameLengthExceeded, QueryLabelNameLengthExceeded};
+use crate::dns::byte_packet_buffer_error::BytePacketBufferError::{EndOfBuffer, ExceededJumpCount, ForwardCompressionPointer, QueryDomainNameLengthExceeded, QueryLabelNameLengthExceeded};
+use crate::dns::name::{Name, MAX_LABEL_LENGTH, MAX_NAME_LENGTH};
+
+/// The largest message which can be carried over UDP without making use of
+/// EDNS(0) to advertise a larger payload size.
+///
+/// RFC 1035
+/// 2.3.4. Size limits
+pub const MAX_UDP_MESSAGE_SIZE: usize = 512;
+
+/// The largest message which can ever be represented, bound by the two byte
+/// length field which prefixes messages sent over TCP.
+///
+/// RFC 1035
+/// 4.2.2. TCP usage
+pub const MAX_MESSAGE_SIZE: usize = 65535;
 
 pub struct BytePacketBuffer {
-    pub buffer: [u8; 512],
-    pub position: usize,
+    buffer: Vec<u8>,
+    position: usize,
+    /// The maximum number of bytes which can be written into the buffer. This
+    /// is independent of the current length of the buffer, allowing writes to
+    /// be limited to the size a client can accept.
+    max_size: usize,
+    /// The offsets of every name (and name suffix) already written into the
+    /// buffer. Used to emit compression pointers for repeated names, which
+    /// are matched case-insensitively.
+    names: HashMap<Name, usize>,
+    /// Whether name compression is allowed when writing. Some contexts, such
+    /// as the DNSSEC canonical form, forbid it.
+    compression: bool,
+    /// Whether reading rejects messages which are malformed but could
+    /// otherwise still be made sense of, such as records whose data does not
+    /// match their RDLENGTH or compression pointers which point forward.
+    strict: bool,
 }
 
 
 impl BytePacketBuffer {
     // Create a fresh buffer for holding a dns record package contents and a
-    // field for keeping track of where we are at.
+    // field for keeping track of where we are at. The buffer is sized for a
+    // classic UDP message of 512 bytes.
     pub fn new() -> BytePacketBuffer {
+        BytePacketBuffer::with_capacity(MAX_UDP_MESSAGE_SIZE)
+    }
+
+    // Create a zeroed buffer of the given size, ready to receive a message of
+    // up to that many bytes. Writes are limited to the same size.
+    pub fn with_capacity(size: usize) -> BytePacketBuffer {
         BytePacketBuffer {
-            buffer: [0; 512],
+            buffer: vec![0; size],
             position: 0,
+            max_size: size,
+            names: HashMap::new(),
+            compression: true,
+            strict: false,
         }
     }
 
+    // Create a buffer holding a copy of an already received message, sized
+    // exactly to its contents.
+    pub fn from_bytes(bytes: &[u8]) -> BytePacketBuffer {
+        let mut buffer = BytePacketBuffer::with_capacity(0);
+        buffer.buffer = bytes.to_vec();
+        buffer.max_size = bytes.len();
+        buffer
+    }
+
+    // Limit the number of bytes that can be written into the buffer, for
+    // example to the UDP payload size advertised by a client. Writing beyond
+    // the current length of the buffer will grow it, up to this limit.
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size.min(MAX_MESSAGE_SIZE);
+    }
+
+    // The maximum number of bytes that can be written into the buffer.
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    // Enable or disable the writing of compression pointers for all names
+    // written into the buffer from this point onwards.
+    pub fn set_compression(&mut self, enabled: bool) {
+        self.compression = enabled;
+    }
+
+    // Enable or disable strict parsing for all reads from this point onwards.
+    pub fn set_strict(&mut self, enabled: bool) {
+        self.strict = enabled;
+    }
+
+    // Whether the buffer is being read in strict mode.
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
     // Get the current position within the buffer.
     pub fn position(&self) -> usize {
         self.position
     }
 
-    // Step the buffer position forward a specific number of steps.
-    pub fn step(&mut self, step: usize) {
-        self.position += step;
+    // The bytes before the current position, which after writing a message
+    // are the message itself.
+    pub fn written(&self) -> &[u8] {
+        &self.buffer[..self.position]
     }
 
-    // Change the buffer position to the given value.
-    fn seek(&mut self, position: usize) {
-        self.position = position
+    // The number of bytes left to be read after the current position.
+    pub fn remaining(&self) -> usize {
+        self.buffer.len().saturating_sub(self.position)
+    }
+
+    // Step the buffer position forward a specific number of steps. Stepping
+    // past the end of the buffer is an error, leaving the position untouched.
+    pub fn step(&mut self, step: usize) -> Result<(), BytePacketBufferError> {
+        match self.position.checked_add(step) {
+            Some(position) if position <= self.buffer.len() => {
+                self.position = position;
+                Ok(())
+            }
+            _ => Err(EndOfBuffer),
+        }
+    }
+
+    // Change the buffer position to the given value, which can be at most the
+    // end of the buffer.
+    pub fn seek(&mut self, position: usize) -> Result<(), BytePacketBufferError> {
+        if position > self.buffer.len() {
+            return Err(EndOfBuffer);
+        }
+
+        self.position = position;
+        Ok(())
+    }
+
+    // Move the position back to an earlier point, discarding everything
+    // written since. Names written after that point are forgotten as well, so
+    // that no later name is compressed against data which is overwritten.
+    pub fn rewind(&mut self, position: usize) {
+        if position < self.position {
+            self.position = position;
+            self.names.retain(|_, offset| *offset < position);
+        }
     }
 
     // Read a single byte and then move the position one step forward.
     pub fn read(&mut self) -> Result<u8, BytePacketBufferError> {
-        if self.position >= 512 {
+        if self.position >= self.buffer.len() {
             return Err(EndOfBuffer);
         }
 
         let result = self.buffer[self.position];
-        self.step(1);
+        self.step(1)?;
 
         Ok(result)
     }
@@ -47,20 +160,20 @@ impl BytePacketBuffer {
     // Get a single byte from the buffer without performing any additional
     // forward stepping.
     fn get(&mut self, position: usize) -> Result<u8, BytePacketBufferError> {
-        if position >= 512 {
+        if position >= self.buffer.len() {
             return Err(EndOfBuffer);
         }
 
         Ok(self.buffer[position])
     }
 
-    // Get a range of bytes from the current buffer.
+    // Get a range of bytes from the current buffer. The range may end exactly
+    // at the end of the buffer, but not beyond it.
     pub fn get_range(&mut self, start: usize, length: usize) -> Result<&[u8], BytePacketBufferError> {
-        if start + length >= 512 {
-            return Err(EndOfBuffer);
+        match start.checked_add(length) {
+            Some(end) if end <= self.buffer.len() => Ok(&self.buffer[start..end]),
+            _ => Err(EndOfBuffer),
         }
-
-        Ok(&self.buffer[start..start + length])
     }
 
     // Read two bytes, stepping two steps forward
@@ -74,7 +187,7 @@ impl BytePacketBuffer {
         let res = ((self.read()? as u32) << 24)
             | ((self.read()? as u32) << 16)
             | ((self.read()? as u32) << 8)
-            | ((self.read()? as u32) << 0);
+            | (self.read()? as u32);
 
         Ok(res)
     }
@@ -82,9 +195,13 @@ impl BytePacketBuffer {
     /// Read a qname
     ///
     /// The Reading domain names, taking labels into consideration.
-    /// Will take something like [3]www[6]google[3]com[0] and append
-    /// www.google.com to outstr.
-    pub fn read_question_name(&mut self) -> Result<String, BytePacketBufferError> {
+    /// Will take something like [3]www[6]google[3]com[0] and return
+    /// the name www.google.com.
+    ///
+    /// The case of every label is kept as it was on the wire, which a
+    /// resolver relies on to check that a response echoes its question
+    /// exactly. Names still compare case-insensitively.
+    pub fn read_question_name(&mut self) -> Result<Name, BytePacketBufferError> {
         // Since we might encounter jumps, we'll keep track of our position
         // locally as opposed to using the position within the struct. This
         // allows us to move the shared position to a point past our current
@@ -101,11 +218,11 @@ impl BytePacketBuffer {
         // against it.
         let max_jumps = 5;
 
-        // Our delimiter which we append for each label. Since we don't want a
-        // dot at the beginning of the domain name we'll leave it empty for now
-        // and set it to "." at the end of the first iteration.
-        let mut delimiter = "";
-        let mut qname = String::new();
+        let mut labels = Vec::new();
+
+        // The length of the name as it would be written without compression,
+        // starting with the terminating zero length octet of the root.
+        let mut name_length = 1;
 
         loop {
             if jumps_performed > max_jumps {
@@ -124,16 +241,24 @@ impl BytePacketBuffer {
             //
             // E.g. 8 bits with the first values set to 1.
             if (word_length & 0xC0) == 0xC0 {
+                // Read another byte, calculate offset and perform the jump by
+                // updating our local positional value.
+                let second_byte = self.get(current_position + 1)? as u16;
+
                 // update the buffer position to a point past the current label.
                 // We don't need to touch it any further.
                 if !jumped {
-                    self.seek(current_position + 2)
+                    self.seek(current_position + 2)?;
                 }
 
-                // Read another byte, calculate offset and perform the jump by
-                // updating our local positional value.
-                let second_byte = self.get(current_position + 1)? as u16;
                 let offset = (((word_length as u16) ^ 0xc0) << 8) | second_byte;
+
+                // Pointers refer to a prior occurrence of a name, one that
+                // points forward can only come from a malformed message.
+                if self.strict && offset as usize >= current_position {
+                    return Err(ForwardCompressionPointer(offset as usize));
+                }
+
                 current_position = offset as usize;
 
                 jumped = true;
@@ -155,50 +280,65 @@ impl BytePacketBuffer {
                 break;
             }
 
-            // Append the delimiter to our finalised string buffer first. This
-            // will be our dot for all situations other than the first append.
-            qname.push_str(delimiter);
+            // The two most significant bits set to 01 or 10 are reserved for
+            // other label types, neither of which are in use, and would
+            // otherwise be taken as a label longer than 63 octets.
+            if self.strict && word_length as usize > MAX_LABEL_LENGTH {
+                return Err(QueryLabelNameLengthExceeded(labels.len(), word_length as usize));
+            }
 
-            let str_buffer = self.get_range(current_position, word_length as usize)?;
-            qname.push_str(&String::from_utf8_lossy(str_buffer).to_lowercase());
+            // The limit on the length of a name applies to the decoded name,
+            // regardless of how it was compressed.
+            name_length += word_length as usize + 1;
+            if name_length > MAX_NAME_LENGTH {
+                return Err(QueryDomainNameLengthExceeded(name_length));
+            }
 
-            // Update our delimiter for all future appends.
-            delimiter = ".";
+            let str_buffer = self.get_range(current_position, word_length as usize)?;
+            labels.push(str_buffer.to_vec());
 
             // move forward the full length of the label.
             current_position += word_length as usize;
         }
 
         if !jumped {
-            self.seek(current_position);
+            self.seek(current_position)?;
         }
 
-        Ok(qname)
+        Ok(Name::from_labels(labels))
     }
 
-    // Set writes a byte at the given position within the buffer and then
-    // does not move or shift the position. Allowing updates to happen after
-    // the fact.
+    // Overwrite a single byte which has already been written, such as a
+    // length field which is only known once the data following it is written.
     pub fn set(&mut self, pos: usize, val: u8) -> Result<(), BytePacketBufferError> {
-        self.buffer[pos] = val;
-        Ok(())
+        match self.buffer.get_mut(pos) {
+            Some(byte) => {
+                *byte = val;
+                Ok(())
+            }
+            None => Err(EndOfBuffer),
+        }
     }
 
     pub fn set_u16(&mut self, pos: usize, val: u16) -> Result<(), BytePacketBufferError> {
         self.set(pos, (val >> 8) as u8)?;
-        self.set(pos + 1, ((val >> 0) & 0xFF) as u8)?;
+        self.set(pos.checked_add(1).ok_or(EndOfBuffer)?, (val & 0xFF) as u8)?;
         Ok(())
     }
 
     // Writes the byte data into the buffer at the given position and takes
     // one step forward within the buffer.
     pub fn write(&mut self, value: u8) -> Result<(), BytePacketBufferError> {
-        if self.position >= 512 {
+        if self.position >= self.max_size {
             return Err(EndOfBuffer);
         }
 
+        if self.position >= self.buffer.len() {
+            self.buffer.resize(self.position + 1, 0);
+        }
+
         self.buffer[self.position] = value;
-        self.step(1);
+        self.step(1)?;
         Ok(())
     }
 
@@ -215,10 +355,11 @@ impl BytePacketBuffer {
         self.write(((val >> 24) & 0xFF) as u8)?;
         self.write(((val >> 16) & 0xFF) as u8)?;
         self.write(((val >> 8) & 0xFF) as u8)?;
-        self.write(((val >> 0) & 0xFF) as u8)
+        self.write((val & 0xFF) as u8)
     }
 
-    // Write the question domain name.
+    // Write the question domain name, compressing it against any names which
+    // have already been written into the buffer.
     //
     // Domain names in messages are expressed in terms of a sequence of labels.
     // Each label is represented as a one octet length field followed by that
@@ -237,26 +378,230 @@ impl BytePacketBuffer {
     // Domain Implementation and Specification
     // November 1987
     // 2.3.4. Size limits
-    pub fn write_question_name(&mut self, value: &str) -> Result<(), BytePacketBufferError> {
-        if value.len() > 255 {
-            return Err(QueryDomainNameLengthExceeded(value.len()));
+    pub fn write_question_name(&mut self, value: &Name) -> Result<(), BytePacketBufferError> {
+        self.write_name(value, self.compression)
+    }
+
+    // Write the domain name in full, without ever replacing any of its labels
+    // with a compression pointer. Used for names which must not be compressed,
+    // regardless of the compression setting of the buffer.
+    pub fn write_question_name_uncompressed(&mut self, value: &Name) -> Result<(), BytePacketBufferError> {
+        self.write_name(value, false)
+    }
+
+    // In order to reduce the size of messages, the domain system utilizes a
+    // compression scheme which eliminates the repetition of domain names in a
+    // message. In this scheme, an entire domain name or a list of labels at
+    // the end of a domain name is replaced with a pointer to a prior occurrence
+    // of the same name.
+    //
+    // The pointer takes the form of a two octet sequence:
+    //
+    //     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    //     | 1  1|                OFFSET                   |
+    //     +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    //
+    // RFC 1035
+    // 4.1.4. Message compression
+    fn write_name(&mut self, value: &Name, compress: bool) -> Result<(), BytePacketBufferError> {
+        if value.wire_length() > MAX_NAME_LENGTH {
+            return Err(QueryDomainNameLengthExceeded(value.wire_length()));
+        }
+
+        for (index, label) in value.labels().iter().enumerate() {
+            if label.len() > MAX_LABEL_LENGTH {
+                return Err(QueryLabelNameLengthExceeded(index, label.len()));
+            }
         }
 
-        for (index, value) in value.split(".").into_iter().enumerate() {
-            if value.len() > 63 {
-                return Err(QueryLabelNameLengthExceeded(index, value.len()));
+        // Walk through the name and every name above it, stopping short of
+        // the root which is always written as the terminating zero.
+        for suffix in value.ancestors().take(value.label_count()) {
+            if compress {
+                if let Some(offset) = self.names.get(&suffix) {
+                    return self.write_u16(0xC000 | (*offset as u16));
+                }
             }
 
             // First go and write the length into the first package bit.
-            self.write_u8(value.len() as u8)?;
+            let label = &suffix.labels()[0];
+            let position = self.position;
+            self.write_u8(label.len() as u8)?;
 
             // Secondly go and write the bytes into the package.
-            for x in value.as_bytes() {
+            for x in label {
                 self.write_u8(*x)?
             }
+
+            // Pointers only have 14 bits available for the offset, so names
+            // written beyond that point can never be referenced.
+            if position <= 0x3FFF {
+                self.names.entry(suffix).or_insert(position);
+            }
         }
 
         // Terminate the domain name with a byte of size zero.
         self.write_u8(0)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use crate::dns::byte_packet_buffer::{BytePacketBuffer, MAX_MESSAGE_SIZE};
+    use crate::dns::byte_packet_buffer_error::BytePacketBufferError;
+    use crate::dns::dns_packet::DnsPacket;
+    use crate::dns::dns_packet_view::DnsPacketView;
+    use crate::dns::dns_question::DnsQuestion;
+    use crate::dns::dns_record::DnsRecord;
+    use crate::dns::query_class::QueryClass;
+    use crate::dns::query_type::QueryType;
+
+    fn packet() -> DnsPacket {
+        let name = |name: &str| name.parse().unwrap();
+
+        let mut packet = DnsPacket::new();
+        packet.questions.push(DnsQuestion::new(name("example.com"), QueryType::A, QueryClass::IN));
+        packet.answers.push(DnsRecord::A { domain: name("www.example.com"), addr: Ipv4Addr::new(10, 0, 0, 1), ttl: 300 });
+        packet.answers.push(DnsRecord::A { domain: name("mail.example.com"), addr: Ipv4Addr::new(10, 0, 0, 2), ttl: 300 });
+        packet.authorities.push(DnsRecord::NS { domain: name("example.com"), host: name("ns1.example.com"), ttl: 300 });
+
+        packet
+    }
+
+    fn write(packet: &DnsPacket, compression: bool) -> Vec<u8> {
+        let mut buffer = BytePacketBuffer::new();
+        buffer.set_compression(compression);
+        packet.write(&mut buffer, MAX_MESSAGE_SIZE).unwrap();
+
+        buffer.written().to_vec()
+    }
+
+    fn has_pointer(message: &[u8]) -> bool {
+        // Neither the label lengths, the letters of the names nor any of the
+        // other fields written contain a byte with the two upper bits set.
+        message.iter().any(|byte| byte & 0xC0 == 0xC0)
+    }
+
+    #[test]
+    fn repeated_suffixes_are_compressed() {
+        let packet = packet();
+        let compressed = write(&packet, true);
+        let uncompressed = write(&packet, false);
+
+        assert!(has_pointer(&compressed));
+        assert!(compressed.len() < uncompressed.len());
+
+        // The question is written in full, and every later name is replaced
+        // by a pointer to it from "example.com" onwards.
+        assert_eq!(&compressed[12..25], b"\x07example\x03com\x00");
+        assert_eq!(&compressed[29..35], b"\x03www\xC0\x0C");
+    }
+
+    #[test]
+    fn compressed_message_round_trips() {
+        let packet = packet();
+        let message = write(&packet, true);
+
+        let parsed = DnsPacket::from_buffer(&mut BytePacketBuffer::from_bytes(&message)).unwrap();
+
+        assert_eq!(parsed.questions, packet.questions);
+        assert_eq!(parsed.answers, packet.answers);
+        assert_eq!(parsed.authorities, packet.authorities);
+    }
+
+    #[test]
+    fn compression_can_be_turned_off() {
+        let packet = packet();
+        let message = write(&packet, false);
+
+        assert!(!has_pointer(&message));
+
+        let parsed = DnsPacket::from_buffer(&mut BytePacketBuffer::from_bytes(&message)).unwrap();
+
+        assert_eq!(parsed.answers, packet.answers);
+        assert_eq!(parsed.authorities, packet.authorities);
+    }
+
+    #[test]
+    fn truncated_messages_are_rejected() {
+        let packet = packet();
+        let message = write(&packet, true);
+
+        // Every prefix of the message is missing some of the data the header
+        // promises, and must fail to parse rather than panic.
+        for length in 0..message.len() {
+            let result = DnsPacket::from_buffer(&mut BytePacketBuffer::from_bytes(&message[..length]));
+            assert!(matches!(result, Err(error) if matches!(error.error, BytePacketBufferError::EndOfBuffer)), "{}", length);
+        }
+    }
+
+    #[test]
+    fn pointer_loops_are_rejected() {
+        // A name at offset 2 which points to itself, and a pair of names
+        // which point to each other.
+        for bytes in [&[0, 0, 0xC0, 0x02][..], &[0xC0, 0x02, 0xC0, 0x00]] {
+            let mut buffer = BytePacketBuffer::from_bytes(bytes);
+            buffer.seek(2).unwrap();
+
+            assert!(matches!(buffer.read_question_name(), Err(BytePacketBufferError::ExceededJumpCount(_))));
+        }
+    }
+
+    #[test]
+    fn ranges_may_end_at_the_end_of_the_buffer() {
+        let mut buffer = BytePacketBuffer::from_bytes(&[1, 2, 3, 4]);
+
+        assert_eq!(buffer.get_range(1, 3).unwrap(), &[2, 3, 4]);
+        assert_eq!(buffer.get_range(4, 0).unwrap(), &[] as &[u8]);
+        assert!(matches!(buffer.get_range(2, 3), Err(BytePacketBufferError::EndOfBuffer)));
+        assert!(matches!(buffer.get_range(usize::MAX, 2), Err(BytePacketBufferError::EndOfBuffer)));
+
+        // A name whose last label ends exactly at the end of the buffer is
+        // only missing its terminating zero.
+        let mut buffer = BytePacketBuffer::from_bytes(b"\x03com");
+        assert!(matches!(buffer.read_question_name(), Err(BytePacketBufferError::EndOfBuffer)));
+
+        let mut buffer = BytePacketBuffer::from_bytes(b"\x03com\x00");
+        assert_eq!(buffer.read_question_name().unwrap().to_string(), "com");
+        assert_eq!(buffer.position(), 5);
+    }
+
+    #[test]
+    fn arbitrary_bytes_never_panic() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let packet = packet();
+        let message = write(&packet, true);
+
+        for _ in 0..10_000 {
+            // Random messages mostly fail on the header counts, so valid
+            // messages with random bytes changed are parsed as well.
+            let bytes = if rng.gen() {
+                let length = rng.gen_range(0..600);
+                (0..length).map(|_| rng.gen()).collect()
+            } else {
+                let mut bytes = message.clone();
+                for _ in 0..rng.gen_range(1..8) {
+                    let index = rng.gen_range(0..bytes.len());
+                    bytes[index] = rng.gen();
+                }
+                bytes
+            };
+
+            for strict in [false, true] {
+                let mut buffer = BytePacketBuffer::from_bytes(&bytes);
+                buffer.set_strict(strict);
+
+                let _ = DnsPacket::from_buffer(&mut buffer);
+            }
+
+            if let Ok(view) = DnsPacketView::new(&bytes) {
+                view.records().for_each(drop);
+                let _ = view.edns();
+                let _ = view.response();
+            }
+        }
+    }
+}
@@ -14,6 +14,7 @@ pub enum BytePacketBufferError {
     /// The returned query type for the DNS record is not being handled. For
     /// example the returned type is A record and was ignored in the
     /// implementation.
+    #[allow(dead_code)]
     UnhandledDnsQueryType(QueryType),
     /// Each label name within a host being written to the package cannot exceed
     /// the maximum length of 63 characters. The usize provided is the faulting
@@ -27,16 +28,42 @@ pub enum BytePacketBufferError {
     ///
     /// The usize value is the size of the inputted length.
     QueryDomainNameLengthExceeded(usize),
+    /// A compression pointer refers to an offset at or after the pointer
+    /// itself, rather than to a prior occurrence of a name. Only rejected when
+    /// reading in strict mode.
+    ///
+    /// The usize value is the offset being pointed to.
+    ForwardCompressionPointer(usize),
+    /// The data of a record did not consume exactly the number of bytes given
+    /// by its RDLENGTH field. Only rejected when reading in strict mode.
+    ///
+    /// rdlength, consumed
+    RecordDataLengthMismatch(u16, usize),
+    /// Bytes remain after the last record of the message. Only rejected when
+    /// reading in strict mode.
+    ///
+    /// The usize value is the number of bytes remaining.
+    TrailingData(usize),
+    /// A message was written into a buffer which already holds data. Names
+    /// are compressed with pointers from the start of the buffer, so a message
+    /// must always start there.
+    ///
+    /// The usize value is the position the message would have started at.
+    MessageNotAtStart(usize),
 }
 
 impl Display for BytePacketBufferError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        return match self {
+        match self {
             BytePacketBufferError::QueryLabelNameLengthExceeded(index, length) => write!(f, "label in position {:?} exceeded 63 characters ({:?})", index, length),
             BytePacketBufferError::QueryDomainNameLengthExceeded(size)  => write!(f, "domain name exceeded 255 characters ({:?})", size),
             BytePacketBufferError::UnhandledDnsQueryType(t) => write!(f, "unhandled dns query type: {:?}", t),
             BytePacketBufferError::ExceededJumpCount(j) => write!(f, "exceeded jump count {:?}", j),
             BytePacketBufferError::EndOfBuffer => write!(f, "end of buffer"),
+            BytePacketBufferError::ForwardCompressionPointer(offset) => write!(f, "compression pointer to forward offset {:?}", offset),
+            BytePacketBufferError::RecordDataLengthMismatch(length, consumed) => write!(f, "record data of {:?} bytes did not match rdlength {:?}", consumed, length),
+            BytePacketBufferError::TrailingData(remaining) => write!(f, "{:?} bytes of trailing data", remaining),
+            BytePacketBufferError::MessageNotAtStart(position) => write!(f, "message written at position {:?} rather than the start of the buffer", position),
         }
     }
 }
@@ -1,5 +1,6 @@
 use crate::dns::byte_packet_buffer::{BytePacketBuffer};
 use crate::dns::byte_packet_buffer_error::BytePacketBufferError;
+use crate::dns::opcode::Opcode;
 use crate::dns::result_code::ResultCode;
 
 
@@ -54,19 +55,15 @@ pub struct DnsHeader {
 
     /// A four bit field that specifies kind of query in this message. This
     /// value is set by the originator of a query and copied into the response.
-    /// The values are:
-    ///
-    /// * 0     - A standard query (QUERY).
-    /// * 1     - An inverse query (IQUERY).
-    /// * 2     - A server status request (STATUS).
-    /// * 3-15  - Reserved for future use.
-    pub opcode: u8,
+    pub opcode: Opcode,
 
     /// A one bit field that specifies whether this message is a query (0), or a
     /// response (1).
     pub response: bool,
 
-    /// This 4 bit field is set as part of responses.
+    /// This 4 bit field is set as part of responses. When the message makes
+    /// use of EDNS, `DnsPacket` extends it with the upper bits held within the
+    /// OPT record, so it may hold any of the extended codes.
     pub rescode: ResultCode,
 
     /// 1 bit
@@ -111,7 +108,7 @@ impl DnsHeader {
             recursion_desired: false,
             truncated_message: false,
             authoritative_answer: false,
-            opcode: 0,
+            opcode: Opcode::QUERY,
             response: false,
 
             rescode: ResultCode::NOERROR,
@@ -136,10 +133,10 @@ impl DnsHeader {
         self.recursion_desired = (a & (1 << 0)) > 0;
         self.truncated_message = (a & (1 << 1)) > 0;
         self.authoritative_answer = (a & (1 << 2)) > 0;
-        self.opcode = (a >> 3) & 0x0F;
+        self.opcode = Opcode::from_num((a >> 3) & 0x0F);
         self.response = (a & (1 << 7)) > 0;
 
-        self.rescode = ResultCode::from_num(b & 0x0F);
+        self.rescode = ResultCode::from_parts(b & 0x0F, 0);
         self.checking_disabled = (b & (1 << 4)) > 0;
         self.authed_data = (b & (1 << 5)) > 0;
         self.z = (b & (1 << 6)) > 0;
@@ -161,12 +158,12 @@ impl DnsHeader {
             (self.recursion_desired as u8)
                 | ((self.truncated_message as u8) << 1)
                 | ((self.authoritative_answer as u8) << 2)
-                | (self.opcode << 3)
-                | ((self.response as u8) << 7) as u8,
+                | ((self.opcode.to_num() & 0x0F) << 3)
+                | ((self.response as u8) << 7),
         )?;
 
         buffer.write_u8(
-            (self.rescode as u8)
+            self.rescode.header_bits()
                 | ((self.checking_disabled as u8) << 4)
                 | ((self.authed_data as u8) << 5)
                 | ((self.z as u8) << 6)
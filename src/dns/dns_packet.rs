@@ -1,11 +1,17 @@
+use std::fmt::{Display, Formatter};
 use std::net::{IpAddr, Ipv4Addr};
 use crate::dns::byte_packet_buffer::BytePacketBuffer;
 use crate::dns::byte_packet_buffer_error::BytePacketBufferError;
 use crate::dns::dns_header::DnsHeader;
 use crate::dns::dns_question::DnsQuestion;
 use crate::dns::dns_record::DnsRecord;
+use crate::dns::edns::Edns;
+use crate::dns::name::Name;
+use crate::dns::packet_builder::{QueryBuilder, ResponseBuilder};
+use crate::dns::parse_error::{ParseError, Section};
 use crate::dns::query_class::QueryClass;
 use crate::dns::query_type::QueryType;
+use crate::dns::result_code::ResultCode;
 
 /// RFC 1035
 /// 4.1. Format [Page 24]
@@ -46,6 +52,10 @@ pub struct DnsPacket {
     pub answers: Vec<DnsRecord>,
     pub authorities: Vec<DnsRecord>,
     pub resources: Vec<DnsRecord>,
+    /// The EDNS(0) information of the message, taken from the OPT
+    /// pseudo-record in the additional section when present. It is written
+    /// back out as the last record of the additional section.
+    pub edns: Option<Edns>,
 }
 
 impl DnsPacket {
@@ -56,9 +66,22 @@ impl DnsPacket {
             answers: Vec::new(),
             authorities: Vec::new(),
             resources: Vec::new(),
+            edns: None,
         }
     }
 
+    /// Start building a query for the name and type, in the IN class.
+    pub fn query(name: Name, qtype: QueryType) -> QueryBuilder {
+        QueryBuilder::new(DnsQuestion::new(name, qtype, QueryClass::IN))
+    }
+
+    /// Start building the response to the request, copying its id, opcode,
+    /// RD flag and questions.
+    #[allow(dead_code)]
+    pub fn response_to(request: &DnsPacket) -> ResponseBuilder {
+        ResponseBuilder::new(&request.header, request.questions.clone())
+    }
+
     /// It's useful to be able to pick a random A record from a packet. When we
     /// get multiple IPs for a single name, it doesn't matter which one we
     /// choose, so in those cases we can now pick one at random.
@@ -69,25 +92,39 @@ impl DnsPacket {
         }).next()
     }
 
+    /// Every address of the given host found within the answer and additional
+    /// sections, both IPv4 and IPv6.
+    pub fn get_addresses(&self, host: &Name) -> Vec<IpAddr> {
+        self.answers.iter().chain(self.resources.iter())
+            .filter_map(|record| match record {
+                DnsRecord::A { domain, addr, .. } if domain == host => Some(IpAddr::V4(*addr)),
+                DnsRecord::AAAA { domain, addr, .. } if domain == host => Some(IpAddr::V6(*addr)),
+                _ => None
+            })
+            .collect()
+    }
+
     /// A helper function which returns an iterator over all name servers in
     /// the authorities section, represented as (domain, host) tuples
-    pub fn get_ns<'a>(&'a self, question_name: &'a str) -> impl Iterator<Item=(&'a str, &'a str)> {
+    pub fn get_ns<'a>(&'a self, question_name: &'a Name) -> impl Iterator<Item=(&'a Name, &'a Name)> {
         self.authorities.iter().
             // In practice, these are always NS records, in well formatted
             // packages. This will ensure to be explicit and convert the
             // records into tuples which only has the data we require to make
             // it easy to work.
             filter_map(|record| match record {
-                DnsRecord::NS { domain, host, .. } => Some((domain.as_str(), host.as_str())),
+                DnsRecord::NS { domain, host, .. } => Some((domain, host)),
                 _ => None
             })
-            // Discard servers which aren't authoritative to our query
-            .filter(move |(domain, _)| question_name.ends_with(*domain))
+            // Discard servers which aren't authoritative to our query. The
+            // comparison is made on whole labels, so a referral for
+            // `ample.com` is never accepted for `example.com`.
+            .filter(move |(domain, _)| question_name.is_subdomain_of(domain))
     }
 
     /// Return resolved name servers based on the question name. Most name
     /// servers will include the IP address for the NS but not always.
-    pub fn get_resolved_ns(&self, question_name: &str) -> Option<Ipv4Addr> {
+    pub fn get_resolved_ns(&self, question_name: &Name) -> Option<Ipv4Addr> {
         self.get_ns(question_name)
             // Now we need to look for a matching A record in the additional
             // section. Since we just want the first valid record, we can just build
@@ -97,10 +134,10 @@ impl DnsPacket {
                     // Filter for A records where the domain match the host
                     // of the NS record that we are currently processing
                     filter_map(move |record| match record {
-                        DnsRecord::A { domain, addr, .. }  if domain == host => Some(*addr),
+                        DnsRecord::A { domain, addr, .. } if domain == host => Some(*addr),
                         _ => None
                     })
-            }).map(|addr| addr)
+            })
             // Finally, pick the first valid entry.
             .next()
     }
@@ -109,7 +146,7 @@ impl DnsPacket {
     /// won't be any A records in the additional section, and we'll have to
     /// perform *another* lookup in the midst. For this, we introduce a method
     /// for returning the host name of an appropriate name server.
-    pub fn get_unresolved_ns<'a>(&'a self, qname: &'a str) -> Option<&'a str> {
+    pub fn get_unresolved_ns<'a>(&'a self, qname: &'a Name) -> Option<&'a Name> {
         // Get an iterator over the nameservers in the authorities section
         self.get_ns(qname)
             .map(|(_, host)| host)
@@ -117,58 +154,552 @@ impl DnsPacket {
             .next()
     }
 
-    pub fn from_buffer(buffer: &mut BytePacketBuffer) -> Result<DnsPacket, BytePacketBufferError> {
+    /// Negative answers (NXDOMAIN and NODATA) carry the SOA record of the zone
+    /// within the authority section, which is returned here when present.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc2308#section-3
+    pub fn get_soa(&self) -> Option<&DnsRecord> {
+        self.authorities.iter().find(|record| matches!(record, DnsRecord::SOA { .. }))
+    }
+
+    /// The time a negative answer may be cached for, which is the lower of
+    /// the TTL of the SOA record in the authority section and its MINIMUM
+    /// field. Returns `None` when the packet holds no SOA record, in which case
+    /// the negative answer should not be cached.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc2308#section-5
+    pub fn get_negative_ttl(&self) -> Option<u32> {
+        match self.get_soa()? {
+            DnsRecord::SOA { ttl, minimum, .. } => Some((*ttl).min(*minimum)),
+            _ => None,
+        }
+    }
+
+    /// Parse a message from the buffer. Errors report the section and entry
+    /// being read, along with the offset at which that entry starts.
+    ///
+    /// Parsing is lenient by default. Strict mode is enabled on the buffer
+    /// before parsing, after which any bytes left after the last record are
+    /// rejected, along with the malformed records rejected by the buffer
+    /// itself:
+    ///
+    ///     let mut buffer = BytePacketBuffer::from_bytes(bytes);
+    ///     buffer.set_strict(true);
+    ///     let packet = DnsPacket::from_buffer(&mut buffer)?;
+    ///
+    /// Parsing never panics, whatever the contents of the buffer. Truncated,
+    /// malformed or deliberately hostile messages always result in an error.
+    pub fn from_buffer(buffer: &mut BytePacketBuffer) -> Result<DnsPacket, ParseError> {
         let mut result = DnsPacket::new();
-        result.header.read(buffer)?;
+        let offset = buffer.position();
+        result.header.read(buffer).map_err(positioned(Section::Header, 0, offset))?;
 
-        for _ in 0..result.header.questions {
+        for index in 0..result.header.questions as usize {
             let mut question = DnsQuestion::new(
-                "".to_string(),
+                Name::root(),
                 QueryType::UNKNOWN(0),
                 QueryClass::UNKNOWN(0),
             );
 
-            question.read(buffer)?;
+            let offset = buffer.position();
+            question.read(buffer).map_err(positioned(Section::Question, index, offset))?;
             result.questions.push(question);
         }
 
-        for _ in 0..result.header.answers {
-            let rec = DnsRecord::read(buffer)?;
+        for index in 0..result.header.answers as usize {
+            let offset = buffer.position();
+            let rec = DnsRecord::read(buffer).map_err(positioned(Section::Answer, index, offset))?;
             result.answers.push(rec);
         }
-        for _ in 0..result.header.authoritative_entries {
-            let rec = DnsRecord::read(buffer)?;
+        for index in 0..result.header.authoritative_entries as usize {
+            let offset = buffer.position();
+            let rec = DnsRecord::read(buffer).map_err(positioned(Section::Authority, index, offset))?;
             result.authorities.push(rec);
         }
-        for _ in 0..result.header.resource_entries {
-            let rec = DnsRecord::read(buffer)?;
-            result.resources.push(rec);
+        for index in 0..result.header.resource_entries as usize {
+            // The OPT pseudo-record is not a real record and is kept apart
+            // from the rest of the additional section.
+            let offset = buffer.position();
+            match DnsRecord::read(buffer).map_err(positioned(Section::Additional, index, offset))? {
+                DnsRecord::OPT { edns } => result.edns = Some(edns),
+                rec => result.resources.push(rec),
+            }
+        }
+
+        let remaining = buffer.remaining();
+        if buffer.is_strict() && remaining > 0 {
+            return Err(positioned(Section::End, 0, buffer.position())(BytePacketBufferError::TrailingData(remaining)));
+        }
+
+        // With EDNS, the response code is extended by the upper bits held
+        // within the OPT record.
+        if let Some(edns) = &result.edns {
+            result.header.rescode = ResultCode::from_parts(result.header.rescode.header_bits(), edns.extended_rcode);
         }
 
         Ok(result)
     }
 
-    pub fn write(&mut self, buffer: &mut BytePacketBuffer) -> Result<(), BytePacketBufferError> {
-        self.header.questions = self.questions.len() as u16;
-        self.header.answers = self.answers.len() as u16;
-        self.header.authoritative_entries = self.authorities.len() as u16;
-        self.header.resource_entries = self.resources.len() as u16;
+    /// Write the packet into the buffer as a message of at most `max_size`
+    /// bytes. Any limit set on the buffer beforehand is put back afterwards.
+    ///
+    /// Compression pointers are offsets from the start of the buffer, so the
+    /// message must be written at the very start of it.
+    ///
+    /// When the records don't all fit, whole RRsets are dropped from the end
+    /// of the message, so that the question and as much of the answer as
+    /// possible are kept, along with the OPT record. The counts of the header
+    /// written match the records actually written, while the packet itself is
+    /// left untouched, so it can be written again with a larger limit, such
+    /// as when the query is retried over TCP.
+    ///
+    /// RFC 2181
+    /// 9. The TC (truncated) header bit
+    ///
+    /// The TC bit should be set in responses only when an RRSet is required as
+    /// a part of the response, but could not be included in its entirety. The
+    /// TC bit should not be set merely because some extra information could
+    /// have been included if there were room, but wasn't.
+    ///
+    /// Records of the additional section are such extra information, so
+    /// dropping only those leaves the TC bit alone.
+    pub fn write(&self, buffer: &mut BytePacketBuffer, max_size: usize) -> Result<(), BytePacketBufferError> {
+        if buffer.position() != 0 {
+            return Err(BytePacketBufferError::MessageNotAtStart(buffer.position()));
+        }
+
+        let limit = buffer.max_size();
+        let result = self.write_within(buffer, max_size);
+        buffer.set_max_size(limit);
+
+        result
+    }
+
+    fn write_within(&self, buffer: &mut BytePacketBuffer, max_size: usize) -> Result<(), BytePacketBufferError> {
+        let mut header = self.header.clone();
 
-        self.header.write(buffer)?;
+        let edns = self.edns.clone().map(|mut edns| {
+            edns.extended_rcode = header.rescode.extended_bits();
+            edns
+        });
+
+        // Room is kept for the OPT record, which is written last but must
+        // never be dropped.
+        let edns_length = edns.as_ref().map_or(0, |edns| edns.wire_length());
+        buffer.set_max_size(max_size.saturating_sub(edns_length));
+
+        // The header is written again once the counts are known.
+        header.write(buffer)?;
 
         for question in &self.questions {
             question.write(buffer)?;
         }
-        for rec in &self.answers {
-            rec.write(buffer)?;
+
+        let mut truncated = None;
+        let mut counts = [0; 3];
+
+        for (count, (section, records)) in counts.iter_mut().zip([
+            (Section::Answer, &self.answers),
+            (Section::Authority, &self.authorities),
+            (Section::Additional, &self.resources),
+        ]) {
+            while truncated.is_none() && *count < records.len() {
+                let rrset_end = rrset_end(records, *count);
+                let position = buffer.position();
+
+                match records[*count..rrset_end].iter().try_for_each(|rec| rec.write(buffer).map(|_| ())) {
+                    Ok(()) => *count = rrset_end,
+                    Err(BytePacketBufferError::EndOfBuffer) => {
+                        buffer.rewind(position);
+                        truncated = Some(section);
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+        }
+
+        if truncated.is_some_and(|section| section != Section::Additional) {
+            header.truncated_message = true;
+        }
+
+        buffer.set_max_size(max_size);
+
+        if let Some(edns) = &edns {
+            edns.write(buffer)?;
+        }
+
+        header.questions = self.questions.len() as u16;
+        header.answers = counts[0] as u16;
+        header.authoritative_entries = counts[1] as u16;
+        header.resource_entries = (counts[2] + edns.is_some() as usize) as u16;
+
+        let position = buffer.position();
+        buffer.seek(0)?;
+        header.write(buffer)?;
+        buffer.seek(position)?;
+
+        Ok(())
+    }
+}
+
+/// The end of the RRset starting at the given index: the records which follow
+/// it with the same owner, type and class.
+fn rrset_end(records: &[DnsRecord], start: usize) -> usize {
+    let first = &records[start];
+
+    start + 1 + records[start + 1..].iter()
+        .take_while(|rec| rec.domain() == first.domain() && rec.qtype() == first.qtype() && rec.class() == first.class())
+        .count()
+}
+
+/// Packets are displayed in the style of dig: the header, followed by the
+/// EDNS information and then each of the sections in turn, with every
+/// question and record on a line of its own. Empty sections other than the
+/// question section are left out.
+///
+///     ;; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 6666
+///     ;; flags: qr rd ra; QUERY: 1, ANSWER: 1, AUTHORITY: 0, ADDITIONAL: 0
+///
+///     ;; QUESTION SECTION:
+///     ;example.com. IN A
+///
+///     ;; ANSWER SECTION:
+///     example.com. 300 IN A 93.184.215.14
+impl Display for DnsPacket {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let header = &self.header;
+        writeln!(f, ";; ->>HEADER<<- opcode: {}, status: {}, id: {}", header.opcode, header.rescode, header.id)?;
+
+        let flags = [
+            (header.response, "qr"),
+            (header.authoritative_answer, "aa"),
+            (header.truncated_message, "tc"),
+            (header.recursion_desired, "rd"),
+            (header.recursion_available, "ra"),
+            (header.authed_data, "ad"),
+            (header.checking_disabled, "cd"),
+        ];
+
+        write!(f, ";; flags:")?;
+        for (_, flag) in flags.iter().filter(|(set, _)| *set) {
+            write!(f, " {}", flag)?;
+        }
+
+        write!(f, "; QUERY: {}, ANSWER: {}, AUTHORITY: {}, ADDITIONAL: {}",
+               self.questions.len(),
+               self.answers.len(),
+               self.authorities.len(),
+               self.resources.len() + self.edns.is_some() as usize)?;
+
+        if let Some(edns) = &self.edns {
+            write!(f, "\n\n;; OPT PSEUDOSECTION:")?;
+            write!(f, "\n; EDNS: version: {}, flags:{}; udp: {}", edns.version, if edns.dnssec_ok { " do" } else { "" }, edns.udp_payload_size)?;
+
+            for option in &edns.options {
+                write!(f, "\n; OPTION {}: {}", option.code, DnsRecord::generic_rdata(&option.data))?;
+            }
         }
-        for rec in &self.authorities {
-            rec.write(buffer)?;
+
+        write!(f, "\n\n;; QUESTION SECTION:")?;
+        for question in &self.questions {
+            write!(f, "\n;{}", question)?;
         }
-        for rec in &self.resources {
-            rec.write(buffer)?;
+
+        let sections = [
+            ("ANSWER", &self.answers),
+            ("AUTHORITY", &self.authorities),
+            ("ADDITIONAL", &self.resources),
+        ];
+
+        for (name, records) in sections {
+            if records.is_empty() {
+                continue;
+            }
+
+            write!(f, "\n\n;; {} SECTION:", name)?;
+            for record in records {
+                write!(f, "\n{}", record)?;
+            }
         }
 
         Ok(())
     }
 }
+
+// Attach the section and entry being read, along with the offset at which
+// the entry starts, to an error raised while parsing.
+fn positioned(section: Section, index: usize, offset: usize) -> impl FnOnce(BytePacketBufferError) -> ParseError {
+    move |error| ParseError { section, index, offset, error }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use crate::dns::byte_packet_buffer::{BytePacketBuffer, MAX_MESSAGE_SIZE};
+    use crate::dns::byte_packet_buffer_error::BytePacketBufferError;
+    use crate::dns::dns_packet::DnsPacket;
+    use crate::dns::dns_question::DnsQuestion;
+    use crate::dns::dns_record::DnsRecord;
+    use crate::dns::edns::Edns;
+    use crate::dns::name::Name;
+    use crate::dns::parse_error::{ParseError, Section};
+    use crate::dns::query_class::QueryClass;
+    use crate::dns::query_type::QueryType;
+
+    // A message of a question (12..29), two answers (29..45 and 45..61) and
+    // an authority record (61..79).
+    fn message() -> Vec<u8> {
+        let name = |name: &str| name.parse().unwrap();
+
+        let mut packet = DnsPacket::new();
+        packet.questions.push(DnsQuestion::new(name("example.com"), QueryType::A, QueryClass::IN));
+        packet.answers.push(DnsRecord::A { domain: name("example.com"), addr: Ipv4Addr::new(10, 0, 0, 1), ttl: 300 });
+        packet.answers.push(DnsRecord::A { domain: name("example.com"), addr: Ipv4Addr::new(10, 0, 0, 2), ttl: 300 });
+        packet.authorities.push(DnsRecord::NS { domain: name("example.com"), host: name("ns1.example.com"), ttl: 300 });
+
+        let mut buffer = BytePacketBuffer::new();
+        packet.write(&mut buffer, MAX_MESSAGE_SIZE).unwrap();
+        assert_eq!(buffer.position(), 79);
+
+        buffer.written().to_vec()
+    }
+
+    fn parse(message: &[u8], strict: bool) -> Result<DnsPacket, ParseError> {
+        let mut buffer = BytePacketBuffer::from_bytes(message);
+        buffer.set_strict(strict);
+
+        DnsPacket::from_buffer(&mut buffer)
+    }
+
+    fn position(error: ParseError) -> (Section, usize, usize) {
+        (error.section, error.index, error.offset)
+    }
+
+    #[test]
+    fn errors_report_where_the_entry_starts() {
+        let message = message();
+
+        assert_eq!(position(parse(&message[..7], false).unwrap_err()), (Section::Header, 0, 0));
+        assert_eq!(position(parse(&message[..20], false).unwrap_err()), (Section::Question, 0, 12));
+        assert_eq!(position(parse(&message[..50], false).unwrap_err()), (Section::Answer, 1, 45));
+        assert_eq!(position(parse(&message[..70], false).unwrap_err()), (Section::Authority, 0, 61));
+    }
+
+    #[test]
+    fn errors_report_the_additional_section() {
+        let mut message = message();
+
+        // Claim an additional record which isn't there.
+        message[11] = 1;
+
+        let error = parse(&message, false).unwrap_err();
+        assert_eq!(position(error), (Section::Additional, 0, 79));
+    }
+
+    #[test]
+    fn trailing_data_is_only_rejected_in_strict_mode() {
+        let mut message = message();
+        message.extend_from_slice(&[0, 0, 0]);
+
+        assert!(parse(&message, false).is_ok());
+
+        let error = parse(&message, true).unwrap_err();
+        assert!(matches!(error.error, BytePacketBufferError::TrailingData(3)));
+        assert_eq!(position(error), (Section::End, 0, 79));
+    }
+
+    #[test]
+    fn record_length_mismatch_is_only_rejected_in_strict_mode() {
+        let mut message = message();
+
+        // Grow the RDLENGTH of the second answer from 4 to 5 bytes, so that
+        // it claims the first byte of the authority record as well.
+        message[45 + 11] = 5;
+
+        assert!(parse(&message, false).is_ok());
+
+        let error = parse(&message, true).unwrap_err();
+        assert!(matches!(error.error, BytePacketBufferError::RecordDataLengthMismatch(5, 4)));
+        assert_eq!(position(error), (Section::Answer, 1, 45));
+    }
+
+    fn rrset(owner: &str, count: u8) -> Vec<DnsRecord> {
+        (0..count).map(|index| DnsRecord::A { domain: owner.parse().unwrap(), addr: Ipv4Addr::new(10, 0, 0, index), ttl: 300 }).collect()
+    }
+
+    // A response with two RRsets in the answer section and one in the
+    // additional section.
+    fn response() -> DnsPacket {
+        let mut packet = DnsPacket::new();
+        packet.header.response = true;
+        packet.questions.push(DnsQuestion::new("example.com".parse().unwrap(), QueryType::A, QueryClass::IN));
+        packet.answers.extend(rrset("a.example.com", 3));
+        packet.answers.extend(rrset("b.example.com", 3));
+        packet.resources.extend(rrset("c.example.com", 3));
+
+        packet
+    }
+
+    fn write(packet: &DnsPacket, max_size: usize) -> Vec<u8> {
+        let mut buffer = BytePacketBuffer::new();
+        packet.write(&mut buffer, max_size).unwrap();
+
+        buffer.written().to_vec()
+    }
+
+    // Read a written message back in strict mode, which rejects any mismatch
+    // between the counts of the header and the records which follow.
+    fn read(message: &[u8]) -> DnsPacket {
+        parse(message, true).unwrap()
+    }
+
+    #[test]
+    fn whole_rrsets_are_dropped() {
+        let packet = response();
+
+        let mut first_rrset = packet.clone();
+        first_rrset.answers.truncate(3);
+        first_rrset.resources.clear();
+
+        // Room for the first RRset and one record of the second.
+        let limit = write(&first_rrset, MAX_MESSAGE_SIZE).len() + 20;
+        let message = write(&packet, limit);
+        assert!(message.len() <= limit);
+
+        let written = read(&message);
+        assert!(written.header.truncated_message);
+        assert_eq!(written.answers, first_rrset.answers);
+        assert!(written.resources.is_empty());
+    }
+
+    #[test]
+    fn dropping_additional_records_leaves_tc_clear() {
+        let packet = response();
+
+        let mut answers = packet.clone();
+        answers.resources.clear();
+
+        let limit = write(&answers, MAX_MESSAGE_SIZE).len() + 20;
+        let written = read(&write(&packet, limit));
+
+        assert!(!written.header.truncated_message);
+        assert_eq!(written.answers, packet.answers);
+        assert!(written.resources.is_empty());
+    }
+
+    #[test]
+    fn room_is_kept_for_opt() {
+        let mut packet = response();
+        packet.edns = Some(Edns::new(1232));
+
+        let full = write(&packet, MAX_MESSAGE_SIZE);
+        let limit = full.len() - 1;
+        let message = write(&packet, limit);
+        assert!(message.len() <= limit);
+
+        let written = read(&message);
+        assert_eq!(written.edns, packet.edns);
+        assert_eq!(written.header.resource_entries as usize, written.resources.len() + 1);
+        assert!(written.resources.len() < packet.resources.len());
+    }
+
+    #[test]
+    fn counts_match_the_records_written() {
+        let packet = response();
+
+        for limit in 29..write(&packet, MAX_MESSAGE_SIZE).len() {
+            let written = read(&write(&packet, limit));
+
+            assert_eq!(written.header.questions, 1);
+            assert_eq!(written.header.answers as usize, written.answers.len());
+            assert_eq!(written.header.authoritative_entries, 0);
+            assert_eq!(written.header.resource_entries as usize, written.resources.len());
+        }
+    }
+
+    #[test]
+    fn the_limit_of_the_buffer_is_put_back() {
+        let packet = response();
+
+        for max_size in [100, MAX_MESSAGE_SIZE] {
+            let mut buffer = BytePacketBuffer::new();
+            buffer.set_max_size(300);
+
+            packet.write(&mut buffer, max_size).unwrap();
+            assert_eq!(buffer.max_size(), 300);
+        }
+
+        // Also when the message could not be written at all.
+        let mut buffer = BytePacketBuffer::new();
+        buffer.set_max_size(300);
+        assert!(packet.write(&mut buffer, 5).is_err());
+        assert_eq!(buffer.max_size(), 300);
+    }
+
+    #[test]
+    fn messages_are_written_at_the_start_of_the_buffer() {
+        let mut buffer = BytePacketBuffer::new();
+        buffer.write_u16(0).unwrap();
+
+        let result = response().write(&mut buffer, MAX_MESSAGE_SIZE);
+        assert!(matches!(result, Err(BytePacketBufferError::MessageNotAtStart(2))));
+        assert_eq!(buffer.position(), 2);
+    }
+
+    #[test]
+    fn truncating_leaves_the_packet_untouched() {
+        let packet = response();
+        let full = write(&packet, MAX_MESSAGE_SIZE);
+
+        let truncated = read(&write(&packet, 100));
+        assert!(truncated.header.truncated_message);
+
+        // Written again with room for everything, as when retrying over TCP,
+        // nothing is missing.
+        assert_eq!(write(&packet, MAX_MESSAGE_SIZE), full);
+        assert_eq!(packet.answers.len(), 6);
+        assert_eq!(packet.resources.len(), 3);
+        assert!(!packet.header.truncated_message);
+    }
+
+    #[test]
+    fn negative_answers_are_cached_for_the_lower_of_ttl_and_minimum() {
+        let soa = |ttl, minimum| DnsRecord::SOA {
+            domain: "example.com".parse().unwrap(),
+            mname: "ns1.example.com".parse().unwrap(),
+            rname: "hostmaster.example.com".parse().unwrap(),
+            serial: 1,
+            refresh: 7200,
+            retry: 900,
+            expire: 1209600,
+            minimum,
+            ttl,
+        };
+
+        let mut packet = DnsPacket::new();
+        assert_eq!(packet.get_negative_ttl(), None);
+
+        packet.authorities.push(soa(3600, 300));
+        assert_eq!(packet.get_soa(), Some(&soa(3600, 300)));
+        assert_eq!(packet.get_negative_ttl(), Some(300));
+
+        packet.authorities = vec![soa(60, 300)];
+        assert_eq!(packet.get_negative_ttl(), Some(60));
+    }
+
+    #[test]
+    fn referrals_are_matched_on_whole_labels() {
+        let name = |name: &str| -> Name { name.parse().unwrap() };
+
+        let mut packet = DnsPacket::new();
+        packet.authorities.push(DnsRecord::NS { domain: name("ample.com"), host: name("ns1.ample.com"), ttl: 300 });
+        packet.resources.push(DnsRecord::A { domain: name("ns1.ample.com"), addr: Ipv4Addr::new(10, 0, 0, 1), ttl: 300 });
+
+        assert_eq!(packet.get_ns(&name("example.com")).count(), 0);
+        assert_eq!(packet.get_resolved_ns(&name("example.com")), None);
+        assert_eq!(packet.get_resolved_ns(&name("www.ample.com")), Some(Ipv4Addr::new(10, 0, 0, 1)));
+
+        packet.authorities.push(DnsRecord::NS { domain: name("COM"), host: name("ns1.example.net"), ttl: 300 });
+        assert_eq!(packet.get_unresolved_ns(&name("example.com")), Some(&name("ns1.example.net")));
+    }
+}
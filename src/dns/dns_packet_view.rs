@@ -0,0 +1,577 @@
+use crate::dns::byte_packet_buffer::BytePacketBuffer;
+use crate::dns::byte_packet_buffer_error::BytePacketBufferError;
+use crate::dns::byte_packet_buffer_error::BytePacketBufferError::{EndOfBuffer, ExceededJumpCount, QueryDomainNameLengthExceeded};
+use crate::dns::dns_header::DnsHeader;
+use crate::dns::dns_question::DnsQuestion;
+use crate::dns::dns_record::DnsRecord;
+use crate::dns::edns::Edns;
+use crate::dns::name::{Name, MAX_NAME_LENGTH};
+use crate::dns::packet_builder::ResponseBuilder;
+use crate::dns::parse_error::{ParseError, Section};
+use crate::dns::query_class::QueryClass;
+use crate::dns::query_type::QueryType;
+
+/// The header is always the first 12 bytes of a message.
+const HEADER_SIZE: usize = 12;
+
+/// The same guard against cycles of compression pointers as used when reading
+/// names from a `BytePacketBuffer`.
+const MAX_JUMPS: i32 = 5;
+
+/// A borrowed view over a message held in a byte slice.
+///
+/// Only the header is parsed up front. Questions and records are read lazily
+/// as they are iterated, and names are only decoded once asked for, so a
+/// message can be inspected without any allocation. When the full message is
+/// needed, `DnsPacket::from_buffer` parses the same bytes into an owned packet.
+///
+/// Like `DnsPacket::from_buffer`, the view never panics on arbitrary bytes.
+pub struct DnsPacketView<'a> {
+    bytes: &'a [u8],
+    header: DnsHeader,
+}
+
+impl<'a> DnsPacketView<'a> {
+    /// Create a view over the message, reading only its header.
+    pub fn new(bytes: &'a [u8]) -> Result<DnsPacketView<'a>, ParseError> {
+        let mut header = DnsHeader::new();
+
+        let header_bytes = bytes.get(..HEADER_SIZE).ok_or(ParseError {
+            section: Section::Header,
+            index: 0,
+            offset: 0,
+            error: EndOfBuffer,
+        })?;
+
+        // The header is small and fixed in size, so it is read through the
+        // regular buffer rather than having a second implementation.
+        let mut buffer = BytePacketBuffer::from_bytes(header_bytes);
+        header.read(&mut buffer).map_err(|error| ParseError {
+            section: Section::Header,
+            index: 0,
+            offset: 0,
+            error,
+        })?;
+
+        Ok(DnsPacketView { bytes, header })
+    }
+
+    pub fn header(&self) -> &DnsHeader {
+        &self.header
+    }
+
+    /// Iterate over the questions of the message. Iteration ends after the
+    /// first error.
+    pub fn questions(&self) -> Questions<'a> {
+        Questions {
+            bytes: self.bytes,
+            offset: HEADER_SIZE,
+            index: 0,
+            count: self.header.questions as usize,
+        }
+    }
+
+    /// Iterate over the records of the answer, authority and additional
+    /// sections, in that order, including the OPT pseudo-record. Iteration
+    /// ends after the first error.
+    pub fn records(&self) -> Records<'a> {
+        let mut questions = self.questions();
+
+        // The records start directly after the last question, so every
+        // question is skipped over first. An error is reported on the first
+        // call to `next`.
+        let error = questions.by_ref().find_map(|question| question.err());
+
+        Records {
+            bytes: self.bytes,
+            offset: questions.offset,
+            index: 0,
+            counts: [
+                (Section::Answer, self.header.answers as usize),
+                (Section::Authority, self.header.authoritative_entries as usize),
+                (Section::Additional, self.header.resource_entries as usize),
+            ],
+            section: 0,
+            error,
+        }
+    }
+
+    /// The EDNS(0) information of the message, taken from the OPT
+    /// pseudo-record in the additional section when present.
+    pub fn edns(&self) -> Result<Option<Edns>, ParseError> {
+        for record in self.records() {
+            let record = record?;
+
+            if record.section == Section::Additional && record.qtype == QueryType::OPT {
+                let mut buffer = BytePacketBuffer::from_bytes(record.data);
+                let edns = Edns::read(&mut buffer, record.class, record.ttl, record.data.len() as u16)
+                    .map_err(|error| ParseError {
+                        section: record.section,
+                        index: record.index,
+                        offset: record.offset,
+                        error,
+                    })?;
+
+                return Ok(Some(edns));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Start building the response to this message, as `DnsPacket::response_to`
+    /// does for an owned packet. Only the questions are decoded, to be copied
+    /// into the response.
+    pub fn response(&self) -> Result<ResponseBuilder, ParseError> {
+        let mut questions = Vec::with_capacity(self.header.questions as usize);
+
+        for (index, question) in self.questions().enumerate() {
+            let question = question?;
+            let name = question.name.to_name().map_err(|error| ParseError {
+                section: Section::Question,
+                index,
+                offset: question.name.offset,
+                error,
+            })?;
+
+            questions.push(DnsQuestion::new(name, question.q_type, question.q_class));
+        }
+
+        Ok(ResponseBuilder::new(&self.header, questions))
+    }
+}
+
+/// A name within the message, decoded only when asked for.
+#[derive(Clone, Copy, Debug)]
+pub struct NameView<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> NameView<'a> {
+    /// Iterate over the labels of the name, following any compression
+    /// pointers, without copying them.
+    pub fn labels(&self) -> Labels<'a> {
+        Labels {
+            bytes: self.bytes,
+            offset: self.offset,
+            jumps: 0,
+            length: 1,
+            done: false,
+        }
+    }
+
+    /// Decode the name into an owned `Name`, keeping the case of its labels.
+    pub fn to_name(self) -> Result<Name, BytePacketBufferError> {
+        let labels = self.labels()
+            .map(|label| label.map(|label| label.to_vec()))
+            .collect::<Result<Vec<Vec<u8>>, BytePacketBufferError>>()?;
+
+        Ok(Name::from_labels(labels))
+    }
+}
+
+/// An iterator over the labels of a `NameView`.
+pub struct Labels<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    jumps: i32,
+    /// The length of the name so far, as it would be written without
+    /// compression.
+    length: usize,
+    done: bool,
+}
+
+impl<'a> Iterator for Labels<'a> {
+    type Item = Result<&'a [u8], BytePacketBufferError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.read_label();
+        self.done = !matches!(result, Ok(Some(_)));
+        result.transpose()
+    }
+}
+
+impl<'a> Labels<'a> {
+    fn read_label(&mut self) -> Result<Option<&'a [u8]>, BytePacketBufferError> {
+        loop {
+            let length = get(self.bytes, self.offset)?;
+
+            if (length & 0xC0) == 0xC0 {
+                if self.jumps >= MAX_JUMPS {
+                    return Err(ExceededJumpCount(MAX_JUMPS));
+                }
+
+                let second_byte = get(self.bytes, self.offset + 1)? as usize;
+                self.offset = (((length as usize) ^ 0xC0) << 8) | second_byte;
+                self.jumps += 1;
+
+                continue;
+            }
+
+            if length == 0 {
+                return Ok(None);
+            }
+
+            self.length += length as usize + 1;
+            if self.length > MAX_NAME_LENGTH {
+                return Err(QueryDomainNameLengthExceeded(self.length));
+            }
+
+            let start = self.offset + 1;
+            let label = self.bytes.get(start..start + length as usize).ok_or(EndOfBuffer)?;
+            self.offset = start + length as usize;
+
+            return Ok(Some(label));
+        }
+    }
+}
+
+/// A single question of the message.
+#[derive(Clone, Copy, Debug)]
+pub struct QuestionView<'a> {
+    pub name: NameView<'a>,
+    pub q_type: QueryType,
+    pub q_class: QueryClass,
+}
+
+/// An iterator over the questions of a `DnsPacketView`.
+pub struct Questions<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    index: usize,
+    count: usize,
+}
+
+impl<'a> Iterator for Questions<'a> {
+    type Item = Result<QuestionView<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.count {
+            return None;
+        }
+
+        let result = read_question(self.bytes, self.offset).map_err(|error| ParseError {
+            section: Section::Question,
+            index: self.index,
+            offset: self.offset,
+            error,
+        });
+
+        match result {
+            Ok((question, offset)) => {
+                self.offset = offset;
+                self.index += 1;
+                Some(Ok(question))
+            }
+            Err(error) => {
+                self.index = self.count;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+/// A single resource record of the message, with its data left undecoded.
+#[derive(Clone, Copy, Debug)]
+#[allow(dead_code)]
+pub struct RecordView<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    /// The section the record was found in.
+    pub section: Section,
+    /// The index of the record within its section.
+    pub index: usize,
+    pub name: NameView<'a>,
+    pub qtype: QueryType,
+    /// The class of the record, kept as a number as the OPT pseudo-record
+    /// repurposes it to hold the UDP payload size of the sender.
+    pub class: u16,
+    pub ttl: u32,
+    /// The RDATA of the record, exactly RDLENGTH bytes long. Names within it
+    /// may be compressed against the rest of the message.
+    pub data: &'a [u8],
+    /// The offset of the RDATA within the message.
+    pub data_offset: usize,
+}
+
+impl<'a> RecordView<'a> {
+    /// Decode the record into an owned `DnsRecord`.
+    #[allow(dead_code)]
+    pub fn to_record(self) -> Result<DnsRecord, BytePacketBufferError> {
+        let mut buffer = BytePacketBuffer::from_bytes(self.bytes);
+        buffer.seek(self.offset)?;
+
+        DnsRecord::read(&mut buffer)
+    }
+}
+
+/// An iterator over the records of a `DnsPacketView`.
+pub struct Records<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    index: usize,
+    counts: [(Section, usize); 3],
+    section: usize,
+    error: Option<ParseError>,
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Result<RecordView<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            self.section = self.counts.len();
+            return Some(Err(error));
+        }
+
+        // Move on to the next section which still has records left.
+        while self.section < self.counts.len() && self.index >= self.counts[self.section].1 {
+            self.section += 1;
+            self.index = 0;
+        }
+
+        let (section, _) = *self.counts.get(self.section)?;
+
+        match read_record(self.bytes, self.offset, section, self.index) {
+            Ok(record) => {
+                self.offset = record.data_offset + record.data.len();
+                self.index += 1;
+                Some(Ok(record))
+            }
+            Err(error) => {
+                self.section = self.counts.len();
+                Some(Err(ParseError { section, index: self.index, offset: self.offset, error }))
+            }
+        }
+    }
+}
+
+fn get(bytes: &[u8], offset: usize) -> Result<u8, BytePacketBufferError> {
+    bytes.get(offset).copied().ok_or(EndOfBuffer)
+}
+
+fn get_u16(bytes: &[u8], offset: usize) -> Result<u16, BytePacketBufferError> {
+    Ok(((get(bytes, offset)? as u16) << 8) | (get(bytes, offset + 1)? as u16))
+}
+
+fn get_u32(bytes: &[u8], offset: usize) -> Result<u32, BytePacketBufferError> {
+    Ok(((get_u16(bytes, offset)? as u32) << 16) | (get_u16(bytes, offset + 2)? as u32))
+}
+
+// Find the end of the name starting at the given offset, without following
+// any compression pointers, as a pointer always ends a name.
+fn skip_name(bytes: &[u8], mut offset: usize) -> Result<usize, BytePacketBufferError> {
+    loop {
+        let length = get(bytes, offset)?;
+
+        if (length & 0xC0) == 0xC0 {
+            return Ok(offset + 2);
+        }
+
+        if length == 0 {
+            return Ok(offset + 1);
+        }
+
+        offset += length as usize + 1;
+    }
+}
+
+// Read the question at the given offset, returning it along with the offset
+// directly after it.
+fn read_question(bytes: &[u8], offset: usize) -> Result<(QuestionView<'_>, usize), BytePacketBufferError> {
+    let name = NameView { bytes, offset };
+    let offset = skip_name(bytes, offset)?;
+
+    let q_type = QueryType::from_num(get_u16(bytes, offset)?);
+    let q_class = QueryClass::from_num(get_u16(bytes, offset + 2)?);
+
+    Ok((QuestionView { name, q_type, q_class }, offset + 4))
+}
+
+fn read_record(bytes: &[u8], offset: usize, section: Section, index: usize) -> Result<RecordView<'_>, BytePacketBufferError> {
+    let name = NameView { bytes, offset };
+    let fields = skip_name(bytes, offset)?;
+
+    let qtype = QueryType::from_num(get_u16(bytes, fields)?);
+    let class = get_u16(bytes, fields + 2)?;
+    let ttl = get_u32(bytes, fields + 4)?;
+    let data_len = get_u16(bytes, fields + 8)? as usize;
+
+    let data_offset = fields + 10;
+    let data = bytes.get(data_offset..data_offset + data_len).ok_or(EndOfBuffer)?;
+
+    Ok(RecordView { bytes, offset, section, index, name, qtype, class, ttl, data, data_offset })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use crate::dns::byte_packet_buffer::{BytePacketBuffer, MAX_MESSAGE_SIZE};
+    use crate::dns::dns_packet::DnsPacket;
+    use crate::dns::dns_packet_view::DnsPacketView;
+    use crate::dns::dns_question::DnsQuestion;
+    use crate::dns::dns_record::DnsRecord;
+    use crate::dns::edns::{Edns, EdnsOption};
+    use crate::dns::parse_error::{ParseError, Section};
+    use crate::dns::query_class::QueryClass;
+    use crate::dns::query_type::QueryType;
+    use crate::dns::result_code::ResultCode;
+
+    // A response with records in every section, names compressed against
+    // each other and the extended RCODE held partly in the OPT record.
+    fn packet() -> DnsPacket {
+        let name = |name: &str| name.parse().unwrap();
+
+        let mut packet = DnsPacket::new();
+        packet.header.id = 0x1234;
+        packet.header.response = true;
+        packet.header.rescode = ResultCode::BADCOOKIE;
+        packet.questions.push(DnsQuestion::new(name("WWW.Example.com"), QueryType::A, QueryClass::IN));
+        packet.answers.push(DnsRecord::CNAME { domain: name("WWW.Example.com"), host: name("web.example.com"), ttl: 300 });
+        packet.answers.push(DnsRecord::A { domain: name("web.example.com"), addr: Ipv4Addr::new(10, 0, 0, 1), ttl: 300 });
+        packet.authorities.push(DnsRecord::NS { domain: name("example.com"), host: name("ns1.example.com"), ttl: 3600 });
+        packet.resources.push(DnsRecord::A { domain: name("ns1.example.com"), addr: Ipv4Addr::new(10, 0, 0, 53), ttl: 3600 });
+        packet.resources.push(DnsRecord::TXT { domain: name("example.com"), txt: vec![b"hello".to_vec()], ttl: 60 });
+
+        let mut edns = Edns::new(1232);
+        edns.dnssec_ok = true;
+        edns.options.push(EdnsOption { code: 10, data: vec![1, 2, 3, 4, 5, 6, 7, 8] });
+        packet.edns = Some(edns);
+
+        packet
+    }
+
+    fn write(packet: &DnsPacket, compression: bool) -> Vec<u8> {
+        let mut buffer = BytePacketBuffer::with_capacity(MAX_MESSAGE_SIZE);
+        buffer.set_compression(compression);
+        packet.write(&mut buffer, MAX_MESSAGE_SIZE).unwrap();
+
+        buffer.written().to_vec()
+    }
+
+    fn position(error: &ParseError) -> (Section, usize, usize) {
+        (error.section, error.index, error.offset)
+    }
+
+    // Check that the view finds exactly what `DnsPacket::from_buffer` does,
+    // for any message the latter accepts in strict mode. Returns whether the
+    // message was accepted.
+    fn assert_parity(bytes: &[u8]) -> bool {
+        let mut buffer = BytePacketBuffer::from_bytes(bytes);
+        buffer.set_strict(true);
+
+        let Ok(packet) = DnsPacket::from_buffer(&mut buffer) else {
+            return false;
+        };
+
+        let view = DnsPacketView::new(bytes).unwrap();
+        let header = view.header();
+
+        assert_eq!(header.id, packet.header.id);
+        assert_eq!(header.response, packet.header.response);
+        assert_eq!(header.opcode, packet.header.opcode);
+        assert_eq!(header.rescode.header_bits(), packet.header.rescode.header_bits());
+        assert_eq!(header.truncated_message, packet.header.truncated_message);
+        assert_eq!(
+            (header.questions, header.answers, header.authoritative_entries, header.resource_entries),
+            (packet.header.questions, packet.header.answers, packet.header.authoritative_entries, packet.header.resource_entries),
+        );
+
+        let questions: Vec<DnsQuestion> = view.questions()
+            .map(|question| {
+                let question = question.unwrap();
+                DnsQuestion::new(question.name.to_name().unwrap(), question.q_type, question.q_class)
+            })
+            .collect();
+
+        assert_eq!(questions, packet.questions);
+        for (question, expected) in questions.iter().zip(&packet.questions) {
+            assert!(question.q_name.eq_case_sensitive(&expected.q_name));
+        }
+
+        let mut sections = [Vec::new(), Vec::new(), Vec::new()];
+        for record in view.records() {
+            let record = record.unwrap();
+
+            if record.section == Section::Additional && record.qtype == QueryType::OPT {
+                continue;
+            }
+
+            let index = match record.section {
+                Section::Answer => 0,
+                Section::Authority => 1,
+                _ => 2,
+            };
+            sections[index].push(record.to_record().unwrap());
+        }
+
+        assert_eq!(sections, [packet.answers, packet.authorities, packet.resources]);
+        assert_eq!(view.edns().unwrap(), packet.edns);
+
+        true
+    }
+
+    #[test]
+    fn views_match_parsed_packets() {
+        for compression in [true, false] {
+            assert!(assert_parity(&write(&packet(), compression)));
+        }
+
+        // Without EDNS the whole RCODE is in the header.
+        let mut packet = packet();
+        packet.edns = None;
+        packet.header.rescode = ResultCode::NXDOMAIN;
+        assert!(assert_parity(&write(&packet, true)));
+    }
+
+    #[test]
+    fn views_fail_where_parsing_fails() {
+        let message = write(&packet(), true);
+
+        // Every prefix of the message is missing part of a question or
+        // record, which both report at the same place.
+        for length in 0..message.len() {
+            let bytes = &message[..length];
+            let expected = DnsPacket::from_buffer(&mut BytePacketBuffer::from_bytes(bytes)).unwrap_err();
+
+            let error = match DnsPacketView::new(bytes) {
+                Ok(view) => view.records()
+                    .find_map(|record| record.err())
+                    .expect("a truncated message must fail"),
+                Err(error) => error,
+            };
+
+            assert_eq!(position(&error), position(&expected), "{}", length);
+        }
+    }
+
+    #[test]
+    fn views_match_parsed_packets_with_bytes_changed() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let message = write(&packet(), true);
+        let mut accepted = 0;
+
+        for _ in 0..10_000 {
+            let mut bytes = message.clone();
+            for _ in 0..rng.gen_range(1..4) {
+                let index = rng.gen_range(0..bytes.len());
+                bytes[index] = rng.gen();
+            }
+
+            if assert_parity(&bytes) {
+                accepted += 1;
+            }
+        }
+
+        // Changes to a TTL, an address or the text still make for a valid
+        // message, so plenty of those are compared.
+        assert!(accepted > 1000, "{}", accepted);
+    }
+}
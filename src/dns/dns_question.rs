@@ -1,5 +1,7 @@
+use std::fmt::{Display, Formatter};
 use crate::dns::byte_packet_buffer::BytePacketBuffer;
 use crate::dns::byte_packet_buffer_error::BytePacketBufferError;
+use crate::dns::name::Name;
 use crate::dns::query_class::QueryClass;
 use crate::dns::query_type::QueryType;
 
@@ -28,7 +30,7 @@ pub struct DnsQuestion {
     //// name terminates with the zero length octet for the null label of the
     //// root. Note that this field may be an odd number of octets; no padding is
     //// used.
-    pub q_name: String,
+    pub q_name: Name,
     /// A two octet code which specifies the type of the query. The values for
     /// this field include all codes valid for a TYPE field, together with some
     /// more general codes which can match more than one type of RR.
@@ -39,7 +41,7 @@ pub struct DnsQuestion {
 }
 
 impl DnsQuestion {
-    pub fn new(q_name: String, q_type: QueryType, q_class: QueryClass) -> DnsQuestion {
+    pub fn new(q_name: Name, q_type: QueryType, q_class: QueryClass) -> DnsQuestion {
         DnsQuestion {
             q_name,
             q_type,
@@ -66,4 +68,13 @@ impl DnsQuestion {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Questions are displayed as their name, class and type, in the same order
+/// as the fields of a record within a master file. For example
+/// `example.com. IN A`.
+impl Display for DnsQuestion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.q_name.to_fqdn(), self.q_class, self.q_type)
+    }
+}
@@ -1,6 +1,12 @@
+use std::fmt::{Display, Formatter};
 use std::net::{Ipv4Addr, Ipv6Addr};
-use crate::dns::byte_packet_buffer::BytePacketBuffer;
+use std::str::FromStr;
+use crate::dns::byte_packet_buffer::{BytePacketBuffer, MAX_MESSAGE_SIZE};
 use crate::dns::byte_packet_buffer_error::BytePacketBufferError;
+use crate::dns::edns::Edns;
+use crate::dns::name::Name;
+use crate::dns::presentation::{parse_character_string, parse_generic_rdata, tokenize, CharacterString};
+use crate::dns::presentation_error::PresentationError;
 use crate::dns::query_class::QueryClass;
 use crate::dns::query_type::QueryType;
 
@@ -62,10 +68,17 @@ use crate::dns::query_type::QueryType;
 #[allow(dead_code)]
 pub enum DnsRecord {
     // 0
+    //
+    // Records of a type which is not understood are kept as an opaque sequence
+    // of bytes, along with the class they were received with, so they can be
+    // passed on unchanged.
+    //
+    // https://datatracker.ietf.org/doc/html/rfc3597
     UNHANDLED {
-        domain: String,
+        domain: Name,
         qtype: QueryType,
-        data_len: u16,
+        class: QueryClass,
+        data: Vec<u8>,
         ttl: u32,
     },
     // Code 1
@@ -73,7 +86,7 @@ pub enum DnsRecord {
     // A 32 bit IPv4 address is encoded in the data portion of an A resource
     // record in network byte order.
     A {
-        domain: String,
+        domain: Name,
         addr: Ipv4Addr,
         ttl: u32,
     },
@@ -87,8 +100,8 @@ pub enum DnsRecord {
     // NSDNAME: A <domain-name> which specifies a host which should be
     // authoritative for the specified class and domain.
     NS {
-        domain: String,
-        host: String,
+        domain: Name,
+        host: Name,
         ttl: u32,
     },
     // Code 5
@@ -101,8 +114,79 @@ pub enum DnsRecord {
     // CNAME: A <domain-name> which specifies the canonical or primary name for
     // the owner.  The owner name is an alias.
     CNAME {
-        domain: String,
-        host: String,
+        domain: Name,
+        host: Name,
+        ttl: u32,
+    },
+    // Code 6
+    // https://datatracker.ietf.org/doc/html/rfc1035#section-3.3.13
+    //
+    // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    // /                     MNAME                     /
+    // /                                               /
+    // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    // /                     RNAME                     /
+    // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    // |                    SERIAL                     |
+    // |                                               |
+    // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    // |                    REFRESH                    |
+    // |                                               |
+    // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    // |                     RETRY                     |
+    // |                                               |
+    // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    // |                    EXPIRE                     |
+    // |                                               |
+    // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    // |                    MINIMUM                    |
+    // |                                               |
+    // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    //
+    // MNAME: The <domain-name> of the name server that was the original or
+    // primary source of data for this zone.
+    //
+    // RNAME: A <domain-name> which specifies the mailbox of the person
+    // responsible for this zone.
+    //
+    // SERIAL: The unsigned 32 bit version number of the original copy of the
+    // zone.
+    //
+    // REFRESH: A 32 bit time interval before the zone should be refreshed.
+    //
+    // RETRY: A 32 bit time interval that should elapse before a failed
+    // refresh should be retried.
+    //
+    // EXPIRE: A 32 bit time value that specifies the upper limit on the time
+    // interval that can elapse before the zone is no longer authoritative.
+    //
+    // MINIMUM: The unsigned 32 bit minimum TTL field that should be exported
+    // with any RR from this zone. Since RFC 2308 this is the TTL to be used
+    // for negative responses.
+    SOA {
+        domain: Name,
+        mname: Name,
+        rname: Name,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minimum: u32,
+        ttl: u32,
+    },
+    // Code 12
+    // https://datatracker.ietf.org/doc/html/rfc1035#section-3.3.12
+    //
+    // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    // /                   PTRDNAME                    /
+    // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    //
+    // PTRDNAME: A <domain-name> which points to some location in the domain
+    // name space. Most commonly used to map an address, held within the
+    // IN-ADDR.ARPA or IP6.ARPA domains, back to a host name.
+    PTR {
+        domain: Name,
+        host: Name,
         ttl: u32,
     },
     // Code 15
@@ -120,9 +204,9 @@ pub enum DnsRecord {
     // EXCHANGE A <domain-name> which specifies a host willing to act as a mail
     // exchange for the owner name.
     MX {
-        domain: String,
+        domain: Name,
         preference: u16,
-        host: String,
+        host: Name,
         ttl: u32,
     },
     // 28
@@ -137,11 +221,44 @@ pub enum DnsRecord {
     //
     // https://datatracker.ietf.org/doc/html/rfc3596#section-2.2
     AAAA {
-        domain: String,
+        domain: Name,
         addr: Ipv6Addr,
         ttl: u32,
     },
 
+    // Code 33
+    // https://datatracker.ietf.org/doc/html/rfc2782
+    //
+    // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    // |                   PRIORITY                    |
+    // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    // |                    WEIGHT                     |
+    // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    // |                     PORT                      |
+    // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    // /                    TARGET                     /
+    // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    //
+    // PRIORITY: The priority of this target host. A client must attempt to
+    // contact the target host with the lowest-numbered priority it can reach.
+    //
+    // WEIGHT: A relative weight for entries with the same priority. Larger
+    // weights should be given a proportionately higher probability of being
+    // selected.
+    //
+    // PORT: The port on this target host of this service.
+    //
+    // TARGET: The <domain-name> of the target host. A target of "." means that
+    // the service is decidedly not available at this domain.
+    SRV {
+        domain: Name,
+        priority: u16,
+        weight: u16,
+        port: u16,
+        host: Name,
+        ttl: u32,
+    },
+
     // Code 16
     //
     // +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
@@ -153,15 +270,202 @@ pub enum DnsRecord {
     // TXT RRs are used to hold descriptive text. The semantics of the text
     // depends on the domain where it is found.
     //
+    // Each <character-string> is a single length octet followed by that number
+    // of octets, limiting it to 255 bytes. The strings are arbitrary binary
+    // data and are kept as bytes, any string longer than 255 bytes is split
+    // into multiple when written.
+    //
     // https://datatracker.ietf.org/doc/html/rfc1035#section-3.3.14
     TXT {
-        domain: String,
-        txt: String,
+        domain: Name,
+        txt: Vec<Vec<u8>>,
         ttl: u32,
     },
+
+    // Code 41
+    //
+    // The OPT pseudo-record carrying the EDNS(0) information of a message. It
+    // is only ever found in the additional section, and is lifted out into
+    // `DnsPacket::edns` when a packet is read.
+    //
+    // https://datatracker.ietf.org/doc/html/rfc6891#section-6.1
+    OPT {
+        edns: Edns,
+    },
 }
 
 impl DnsRecord {
+    /// The generic presentation format of record data, used for types which
+    /// are not understood: the `\#` token, the length of the data and the data
+    /// itself encoded in hexadecimal. For example `\# 4 0A000001`.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc3597#section-5
+    pub fn generic_rdata(data: &[u8]) -> String {
+        let mut result = format!("\\# {}", data.len());
+
+        if !data.is_empty() {
+            result.push(' ');
+
+            for x in data {
+                result.push_str(&format!("{:02X}", x));
+            }
+        }
+
+        result
+    }
+
+    /// The strings of a TXT record joined into a single value, as expected by
+    /// consumers such as SPF and DKIM which split long values over multiple
+    /// strings. Any other type of record results in `None`.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc7208#section-3.3
+    pub fn txt_string(&self) -> Option<String> {
+        match self {
+            DnsRecord::TXT { txt, .. } => Some(String::from_utf8_lossy(&txt.concat()).to_string()),
+            _ => None
+        }
+    }
+
+    /// The owner name of the record. The OPT pseudo-record has no owner of its
+    /// own, and always belongs to the root.
+    pub fn domain(&self) -> Option<&Name> {
+        match self {
+            DnsRecord::UNHANDLED { domain, .. }
+            | DnsRecord::A { domain, .. }
+            | DnsRecord::NS { domain, .. }
+            | DnsRecord::CNAME { domain, .. }
+            | DnsRecord::SOA { domain, .. }
+            | DnsRecord::PTR { domain, .. }
+            | DnsRecord::MX { domain, .. }
+            | DnsRecord::AAAA { domain, .. }
+            | DnsRecord::SRV { domain, .. }
+            | DnsRecord::TXT { domain, .. } => Some(domain),
+            DnsRecord::OPT { .. } => None,
+        }
+    }
+
+    /// The type of the record.
+    pub fn qtype(&self) -> QueryType {
+        match self {
+            DnsRecord::UNHANDLED { qtype, .. } => *qtype,
+            DnsRecord::A { .. } => QueryType::A,
+            DnsRecord::NS { .. } => QueryType::NS,
+            DnsRecord::CNAME { .. } => QueryType::CNAME,
+            DnsRecord::SOA { .. } => QueryType::SOA,
+            DnsRecord::PTR { .. } => QueryType::PTR,
+            DnsRecord::MX { .. } => QueryType::MX,
+            DnsRecord::AAAA { .. } => QueryType::AAAA,
+            DnsRecord::SRV { .. } => QueryType::SRV,
+            DnsRecord::TXT { .. } => QueryType::TXT,
+            DnsRecord::OPT { .. } => QueryType::OPT,
+        }
+    }
+
+    /// The class of the record. The known types are only kept for the IN
+    /// class, while the OPT pseudo-record uses the class to hold the UDP
+    /// payload size of the sender.
+    pub fn class(&self) -> QueryClass {
+        match self {
+            DnsRecord::UNHANDLED { class, .. } => *class,
+            DnsRecord::OPT { edns } => QueryClass::from_num(edns.udp_payload_size),
+            _ => QueryClass::IN,
+        }
+    }
+
+    /// The TTL of the record. The OPT pseudo-record uses the TTL to hold the
+    /// extended RCODE, the version and the flags.
+    pub fn ttl(&self) -> u32 {
+        match self {
+            DnsRecord::UNHANDLED { ttl, .. }
+            | DnsRecord::A { ttl, .. }
+            | DnsRecord::NS { ttl, .. }
+            | DnsRecord::CNAME { ttl, .. }
+            | DnsRecord::SOA { ttl, .. }
+            | DnsRecord::PTR { ttl, .. }
+            | DnsRecord::MX { ttl, .. }
+            | DnsRecord::AAAA { ttl, .. }
+            | DnsRecord::SRV { ttl, .. }
+            | DnsRecord::TXT { ttl, .. } => *ttl,
+            DnsRecord::OPT { edns } => edns.ttl(),
+        }
+    }
+
+    /// The data of the record in its presentation format, as written within
+    /// a master file. Every name is written fully qualified, and types which
+    /// are not understood are given in the generic format of RFC 3597, as is
+    /// the OPT pseudo-record which has no presentation format of its own.
+    pub fn rdata_string(&self) -> String {
+        match self {
+            DnsRecord::UNHANDLED { data, .. } => DnsRecord::generic_rdata(data),
+            DnsRecord::A { addr, .. } => addr.to_string(),
+            DnsRecord::NS { host, .. }
+            | DnsRecord::CNAME { host, .. }
+            | DnsRecord::PTR { host, .. } => host.to_fqdn(),
+            DnsRecord::SOA { mname, rname, serial, refresh, retry, expire, minimum, .. } => {
+                format!("{} {} {} {} {} {} {}", mname.to_fqdn(), rname.to_fqdn(), serial, refresh, retry, expire, minimum)
+            }
+            DnsRecord::MX { preference, host, .. } => format!("{} {}", preference, host.to_fqdn()),
+            DnsRecord::AAAA { addr, .. } => addr.to_string(),
+            DnsRecord::SRV { priority, weight, port, host, .. } => {
+                format!("{} {} {} {}", priority, weight, port, host.to_fqdn())
+            }
+            DnsRecord::TXT { txt, .. } => {
+                // A record without any strings is written as a single empty
+                // string, which is also how it is written to the wire.
+                if txt.is_empty() {
+                    return "\"\"".to_string();
+                }
+
+                txt.iter()
+                    .map(|value| CharacterString(value).to_string())
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            }
+            DnsRecord::OPT { edns } => DnsRecord::generic_rdata(&edns.data()),
+        }
+    }
+
+    /// The RDATA of the record as it is written to the wire, without any
+    /// compression.
+    pub fn rdata(&self) -> Result<Vec<u8>, BytePacketBufferError> {
+        let mut buffer = BytePacketBuffer::with_capacity(MAX_MESSAGE_SIZE);
+        buffer.set_compression(false);
+        self.write(&mut buffer)?;
+
+        // The data follows the owner name along with the type, class, TTL and
+        // RDLENGTH fields, which take up ten bytes.
+        let start = self.domain().map_or(1, |domain| domain.wire_length()) + 10;
+        Ok(buffer.written()[start..].to_vec())
+    }
+
+    /// Build a record from the fields and data of a record as found on the
+    /// wire, such as record data given in the generic format of RFC 3597.
+    fn from_wire(domain: &Name, qtype: QueryType, class: QueryClass, ttl: u32, data: &[u8]) -> Result<DnsRecord, BytePacketBufferError> {
+        let mut buffer = BytePacketBuffer::with_capacity(MAX_MESSAGE_SIZE);
+        buffer.set_compression(false);
+
+        buffer.write_question_name(domain)?;
+        buffer.write_u16(qtype.to_num())?;
+        buffer.write_u16(class.to_num())?;
+        buffer.write_u32(ttl)?;
+        buffer.write_u16(u16::try_from(data.len()).map_err(|_| BytePacketBufferError::EndOfBuffer)?)?;
+
+        for x in data {
+            buffer.write_u8(*x)?;
+        }
+
+        // The data must make up exactly one record of the given type.
+        let mut buffer = BytePacketBuffer::from_bytes(buffer.written());
+        buffer.set_strict(true);
+
+        let record = DnsRecord::read(&mut buffer)?;
+        if buffer.remaining() > 0 {
+            return Err(BytePacketBufferError::TrailingData(buffer.remaining()));
+        }
+
+        Ok(record)
+    }
+
     pub fn read(buffer: &mut BytePacketBuffer) -> Result<DnsRecord, BytePacketBufferError> {
         let domain = buffer.read_question_name()?;
 
@@ -169,13 +473,36 @@ impl DnsRecord {
         let qtype = QueryType::from_num(qtype_num);
 
         // two octets which specify the class of the data in the RDATA field.
-        // This is currently ignored here since we don't use it for any values
-        // within our record.
-        let _ = buffer.read_u16()?;
+        // This is only used by the OPT record, which repurposes it to hold the
+        // UDP payload size of the sender.
+        let class = buffer.read_u16()?;
 
         let ttl = buffer.read_u32()?;
         let data_len = buffer.read_u16()?;
 
+        let start = buffer.position();
+        let record = DnsRecord::read_data(buffer, domain, qtype, class, ttl, data_len)?;
+
+        // The data of every record must take up exactly the number of bytes
+        // given by RDLENGTH, anything else points at a malformed record.
+        let consumed = buffer.position() - start;
+        if buffer.is_strict() && consumed != data_len as usize {
+            return Err(BytePacketBufferError::RecordDataLengthMismatch(data_len, consumed));
+        }
+
+        Ok(record)
+    }
+
+    // Read the data of a record of the given type, following its RDLENGTH.
+    fn read_data(buffer: &mut BytePacketBuffer, domain: Name, qtype: QueryType, class: u16, ttl: u32, data_len: u16) -> Result<DnsRecord, BytePacketBufferError> {
+        // The data of the known types is only modelled for the IN class, so
+        // records of any other class (such as the CH TXT record answering
+        // `version.bind`) are kept verbatim along with their class, the same
+        // as when they are parsed from text.
+        if QueryClass::from_num(class) != QueryClass::IN && qtype != QueryType::OPT {
+            return DnsRecord::read_unhandled(buffer, domain, qtype, class, ttl, data_len);
+        }
+
         match qtype {
             QueryType::A => {
                 let raw_addr = buffer.read_u32()?;
@@ -183,7 +510,7 @@ impl DnsRecord {
                     ((raw_addr >> 24) & 0xFF) as u8,
                     ((raw_addr >> 16) & 0xFF) as u8,
                     ((raw_addr >> 8) & 0xFF) as u8,
-                    ((raw_addr >> 0) & 0xFF) as u8,
+                    (raw_addr & 0xFF) as u8,
                 );
 
                 Ok(DnsRecord::A { domain, addr, ttl })
@@ -197,13 +524,13 @@ impl DnsRecord {
 
                 let addr = Ipv6Addr::new(
                     ((raw_addr1 >> 16) & 0xFFFF) as u16,
-                    ((raw_addr1 >> 0) & 0xFFFF) as u16,
+                    (raw_addr1 & 0xFFFF) as u16,
                     ((raw_addr2 >> 16) & 0xFFFF) as u16,
-                    ((raw_addr2 >> 0) & 0xFFFF) as u16,
+                    (raw_addr2 & 0xFFFF) as u16,
                     ((raw_addr3 >> 16) & 0xFFFF) as u16,
-                    ((raw_addr3 >> 0) & 0xFFFF) as u16,
+                    (raw_addr3 & 0xFFFF) as u16,
                     ((raw_addr4 >> 16) & 0xFFFF) as u16,
-                    ((raw_addr4 >> 0) & 0xFFFF) as u16,
+                    (raw_addr4 & 0xFFFF) as u16,
                 );
 
                 Ok(DnsRecord::AAAA { domain, addr, ttl })
@@ -216,34 +543,75 @@ impl DnsRecord {
                 let host = buffer.read_question_name()?;
                 Ok(DnsRecord::CNAME { domain, host, ttl })
             }
+            QueryType::PTR => {
+                let host = buffer.read_question_name()?;
+                Ok(DnsRecord::PTR { domain, host, ttl })
+            }
+            QueryType::SOA => {
+                let mname = buffer.read_question_name()?;
+                let rname = buffer.read_question_name()?;
+                let serial = buffer.read_u32()?;
+                let refresh = buffer.read_u32()?;
+                let retry = buffer.read_u32()?;
+                let expire = buffer.read_u32()?;
+                let minimum = buffer.read_u32()?;
+
+                Ok(DnsRecord::SOA { domain, mname, rname, serial, refresh, retry, expire, minimum, ttl })
+            }
             QueryType::MX => {
                 let priority = buffer.read_u16()?;
                 let host = buffer.read_question_name()?;
                 Ok(DnsRecord::MX { domain, preference: priority, host, ttl })
             }
 
+            QueryType::SRV => {
+                let priority = buffer.read_u16()?;
+                let weight = buffer.read_u16()?;
+                let port = buffer.read_u16()?;
+                let host = buffer.read_question_name()?;
+                Ok(DnsRecord::SRV { domain, priority, weight, port, host, ttl })
+            }
+
             QueryType::TXT => {
-                let mut data: Vec<u8> = vec![];
+                let mut txt = Vec::new();
+                let end = buffer.position() + data_len as usize;
+
+                while buffer.position() < end {
+                    let length = buffer.read()?;
 
-                for _ in 0..data_len {
-                    data.push(buffer.read()?);
+                    let mut data = Vec::with_capacity(length as usize);
+                    for _ in 0..length {
+                        data.push(buffer.read()?);
+                    }
+
+                    txt.push(data);
                 }
 
-                let txt = String::from_utf8_lossy(&data[..]).to_string();
                 Ok(DnsRecord::TXT { domain, txt, ttl })
             }
 
-            _ => {
-                // For every single unhandled message within the buffer, go and
-                // skip the size of the data and continue execution.
-                buffer.step(data_len as usize);
-
-                let qtype = QueryType::from_num(qtype_num);
-                Ok(DnsRecord::UNHANDLED { domain, qtype, data_len, ttl })
+            QueryType::OPT => {
+                let edns = Edns::read(buffer, class, ttl, data_len)?;
+                Ok(DnsRecord::OPT { edns })
             }
+
+            _ => DnsRecord::read_unhandled(buffer, domain, qtype, class, ttl, data_len),
         }
     }
 
+    // For every single unhandled record within the buffer, go and copy the
+    // data verbatim so that the record can be written back out exactly as it
+    // was received.
+    fn read_unhandled(buffer: &mut BytePacketBuffer, domain: Name, qtype: QueryType, class: u16, ttl: u32, data_len: u16) -> Result<DnsRecord, BytePacketBufferError> {
+        let mut data = Vec::with_capacity(data_len as usize);
+        for _ in 0..data_len {
+            data.push(buffer.read()?);
+        }
+
+        let class = QueryClass::from_num(class);
+        Ok(DnsRecord::UNHANDLED { domain, qtype, class, data, ttl })
+    }
+
     pub fn write(&self, buffer: &mut BytePacketBuffer) -> Result<usize, BytePacketBufferError> {
         let start_pos = buffer.position();
 
@@ -265,8 +633,24 @@ impl DnsRecord {
                 buffer.write_u8(octets[2])?;
                 buffer.write_u8(octets[3])?;
             }
-            DnsRecord::UNHANDLED { .. } => {
-                println!("Skipping record: {:?}", self);
+            DnsRecord::UNHANDLED {
+                ref domain,
+                qtype,
+                ref class,
+                ref data,
+                ttl,
+            } => {
+                buffer.write_question_name(domain)?;
+                buffer.write_u16(qtype.to_num())?;
+                buffer.write_u16(class.to_num())?;
+                buffer.write_u32(ttl)?;
+                buffer.write_u16(data.len() as u16)?;
+
+                // RFC 3597 forbids compression within the data of unknown
+                // types, so it can safely be written verbatim.
+                for x in data {
+                    buffer.write_u8(*x)?;
+                }
             }
 
             DnsRecord::NS {
@@ -318,6 +702,60 @@ impl DnsRecord {
                 let size = (buffer.position() - (pos + 2)) as u16;
                 buffer.set_u16(pos, size)?;
             }
+            DnsRecord::PTR {
+                ref domain,
+                ref host,
+                ttl,
+            } => {
+                buffer.write_question_name(domain)?;
+                buffer.write_u16(QueryType::PTR.to_num())?;
+                buffer.write_u16(QueryClass::IN.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                // Set the size as zero for the DNS record to be zero, since we
+                // don't explicitly know the size until after we have written
+                // all the data.
+                let pos = buffer.position();
+                buffer.write_u16(0)?;
+
+                buffer.write_question_name(host)?;
+
+                let size = buffer.position() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            }
+            DnsRecord::SOA {
+                ref domain,
+                ref mname,
+                ref rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+                ttl,
+            } => {
+                buffer.write_question_name(domain)?;
+                buffer.write_u16(QueryType::SOA.to_num())?;
+                buffer.write_u16(QueryClass::IN.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                // Set the size as zero for the DNS record to be zero, since we
+                // don't explicitly know the size until after we have written
+                // all the data.
+                let pos = buffer.position();
+                buffer.write_u16(0)?;
+
+                buffer.write_question_name(mname)?;
+                buffer.write_question_name(rname)?;
+                buffer.write_u32(serial)?;
+                buffer.write_u32(refresh)?;
+                buffer.write_u32(retry)?;
+                buffer.write_u32(expire)?;
+                buffer.write_u32(minimum)?;
+
+                let size = buffer.position() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            }
             DnsRecord::MX {
                 ref domain,
                 ref host,
@@ -362,6 +800,37 @@ impl DnsRecord {
                 }
             }
 
+            DnsRecord::SRV {
+                ref domain,
+                priority,
+                weight,
+                port,
+                ref host,
+                ttl,
+            } => {
+                buffer.write_question_name(domain)?;
+                buffer.write_u16(QueryType::SRV.to_num())?;
+                buffer.write_u16(QueryClass::IN.to_num())?;
+                buffer.write_u32(ttl)?;
+
+                // Set the size as zero for the DNS record to be zero, since we
+                // don't explicitly know the size until after we have written
+                // all the data.
+                let pos = buffer.position();
+                buffer.write_u16(0)?;
+
+                buffer.write_u16(priority)?;
+                buffer.write_u16(weight)?;
+                buffer.write_u16(port)?;
+
+                // Unlike most other records, RFC 2782 forbids the use of name
+                // compression for the target.
+                buffer.write_question_name_uncompressed(host)?;
+
+                let size = buffer.position() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            }
+
             DnsRecord::TXT {
                 ref domain,
                 ref txt,
@@ -372,16 +841,298 @@ impl DnsRecord {
                 buffer.write_u16(QueryClass::IN.to_num())?;
                 buffer.write_u32(ttl)?;
 
-                let bytes_txt = txt.as_bytes();
+                // Set the size as zero for the DNS record to be zero, since we
+                // don't explicitly know the size until after we have written
+                // all the data.
+                let pos = buffer.position();
+                buffer.write_u16(0)?;
+
+                // The data must hold at least one string, even if empty.
+                if txt.is_empty() {
+                    buffer.write_u8(0)?;
+                }
 
-                buffer.write_u16(bytes_txt.len() as u16)?;
+                for value in txt {
+                    if value.is_empty() {
+                        buffer.write_u8(0)?;
+                    }
 
-                for x in bytes_txt {
-                    buffer.write_u8(*x)?;
+                    // Values beyond the 255 byte limit of a single string are
+                    // split up, consumers are expected to join them back up.
+                    for chunk in value.chunks(255) {
+                        buffer.write_u8(chunk.len() as u8)?;
+
+                        for x in chunk {
+                            buffer.write_u8(*x)?;
+                        }
+                    }
                 }
+
+                let size = buffer.position() - (pos + 2);
+                buffer.set_u16(pos, size as u16)?;
+            }
+
+            DnsRecord::OPT { ref edns } => {
+                edns.write(buffer)?;
             }
         }
 
         Ok(buffer.position() - start_pos)
     }
-}
\ No newline at end of file
+}
+
+/// RFC 1035
+/// 5.1. Format
+///
+/// Records are displayed as a single line of a master file, holding the owner
+/// name, TTL, class, type and data separated by spaces. For example
+/// `example.com. 300 IN MX 10 mail.example.com.`.
+impl Display for DnsRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let owner = self.domain().map_or(".".to_string(), |domain| domain.to_fqdn());
+        write!(f, "{} {} {} {} {}", owner, self.ttl(), self.class(), self.qtype(), self.rdata_string())
+    }
+}
+
+/// Parse a record from a single line of a master file, in the same form as it
+/// is displayed. The TTL and class are optional and may be given in either
+/// order, defaulting to zero and IN. The data of any type, including the known
+/// ones, may be given in the generic format of RFC 3597.
+///
+/// The known types are only represented within the IN class, so records of
+/// another class are kept as unhandled records along with their class.
+impl FromStr for DnsRecord {
+    type Err = PresentationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || PresentationError::InvalidRecord(s.to_string());
+
+        let tokens = tokenize(s)?;
+        let (owner, mut rest) = tokens.split_first().ok_or_else(invalid)?;
+        let domain: Name = owner.parse()?;
+
+        let mut ttl = None;
+        let mut class = None;
+
+        loop {
+            let token = rest.first().ok_or_else(invalid)?;
+
+            if ttl.is_none() && token.bytes().all(|x| x.is_ascii_digit()) {
+                ttl = Some(token.parse::<u32>().map_err(|_| invalid())?);
+            } else if let (None, Ok(value)) = (class, token.parse::<QueryClass>()) {
+                class = Some(value);
+            } else {
+                break;
+            }
+
+            rest = &rest[1..];
+        }
+
+        let ttl = ttl.unwrap_or(0);
+        let class = class.unwrap_or(QueryClass::IN);
+
+        let (qtype, data) = rest.split_first().ok_or_else(invalid)?;
+        let qtype: QueryType = qtype.parse()?;
+
+        if let Some(rdata) = parse_generic_rdata(data) {
+            let rdata = rdata?;
+
+            if class != QueryClass::IN && qtype != QueryType::OPT {
+                return Ok(DnsRecord::UNHANDLED { domain, qtype, class, data: rdata, ttl });
+            }
+
+            return DnsRecord::from_wire(&domain, qtype, class, ttl, &rdata).map_err(|_| invalid());
+        }
+
+        let name = |index: usize| -> Result<Name, PresentationError> {
+            data.get(index).ok_or_else(invalid)?.parse()
+        };
+        let number = |index: usize| -> Result<u32, PresentationError> {
+            data.get(index).ok_or_else(invalid)?.parse().map_err(|_| invalid())
+        };
+        let short = |index: usize| -> Result<u16, PresentationError> {
+            data.get(index).ok_or_else(invalid)?.parse().map_err(|_| invalid())
+        };
+
+        let (record, fields) = match qtype {
+            QueryType::A => {
+                let addr = data.first().ok_or_else(invalid)?.parse().map_err(|_| invalid())?;
+                (DnsRecord::A { domain, addr, ttl }, 1)
+            }
+            QueryType::AAAA => {
+                let addr = data.first().ok_or_else(invalid)?.parse().map_err(|_| invalid())?;
+                (DnsRecord::AAAA { domain, addr, ttl }, 1)
+            }
+            QueryType::NS => (DnsRecord::NS { domain, host: name(0)?, ttl }, 1),
+            QueryType::CNAME => (DnsRecord::CNAME { domain, host: name(0)?, ttl }, 1),
+            QueryType::PTR => (DnsRecord::PTR { domain, host: name(0)?, ttl }, 1),
+            QueryType::SOA => {
+                let record = DnsRecord::SOA {
+                    domain,
+                    mname: name(0)?,
+                    rname: name(1)?,
+                    serial: number(2)?,
+                    refresh: number(3)?,
+                    retry: number(4)?,
+                    expire: number(5)?,
+                    minimum: number(6)?,
+                    ttl,
+                };
+
+                (record, 7)
+            }
+            QueryType::MX => (DnsRecord::MX { domain, preference: short(0)?, host: name(1)?, ttl }, 2),
+            QueryType::SRV => {
+                let record = DnsRecord::SRV {
+                    domain,
+                    priority: short(0)?,
+                    weight: short(1)?,
+                    port: short(2)?,
+                    host: name(3)?,
+                    ttl,
+                };
+
+                (record, 4)
+            }
+            QueryType::TXT => {
+                let txt = data.iter()
+                    .map(|token| parse_character_string(token))
+                    .collect::<Result<Vec<Vec<u8>>, PresentationError>>()?;
+
+                if txt.is_empty() {
+                    return Err(invalid());
+                }
+
+                let fields = txt.len();
+                (DnsRecord::TXT { domain, txt, ttl }, fields)
+            }
+            // Every other type only has the generic format available.
+            _ => return Err(invalid()),
+        };
+
+        if data.len() != fields {
+            return Err(invalid());
+        }
+
+        if class != QueryClass::IN {
+            let data = record.rdata().map_err(|_| invalid())?;
+            return Ok(DnsRecord::UNHANDLED { domain: record.domain().cloned().unwrap_or_default(), qtype, class, data, ttl });
+        }
+
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dns::byte_packet_buffer::{BytePacketBuffer, MAX_MESSAGE_SIZE};
+    use crate::dns::dns_record::DnsRecord;
+    use crate::dns::query_class::QueryClass;
+    use crate::dns::query_type::QueryType;
+
+    // version.bind. 0 CH TXT "x"
+    const VERSION_BIND: &[u8] = b"\x07version\x04bind\x00\x00\x10\x00\x03\x00\x00\x00\x00\x00\x02\x01x";
+
+    // example.com. 300 IN TYPE65280 \# 4 DEADBEEF
+    const PRIVATE_USE: &[u8] = b"\x07example\x03com\x00\xFF\x00\x00\x01\x00\x00\x01\x2C\x00\x04\xDE\xAD\xBE\xEF";
+
+    #[test]
+    fn records_outside_of_in_keep_their_class() {
+        let record = DnsRecord::read(&mut BytePacketBuffer::from_bytes(VERSION_BIND)).unwrap();
+
+        assert_eq!(record.class(), QueryClass::CH);
+        assert_eq!(record.qtype(), QueryType::TXT);
+        assert_eq!(record.to_string(), "version.bind. 0 CH TXT \\# 2 0178");
+
+        let mut buffer = BytePacketBuffer::new();
+        record.write(&mut buffer).unwrap();
+        assert_eq!(buffer.written(), VERSION_BIND);
+    }
+
+    #[test]
+    fn records_outside_of_in_match_their_text_form() {
+        let parsed: DnsRecord = "version.bind. 0 CH TXT \"x\"".parse().unwrap();
+        let read = DnsRecord::read(&mut BytePacketBuffer::from_bytes(VERSION_BIND)).unwrap();
+
+        assert_eq!(parsed, read);
+        assert_eq!(read.to_string().parse::<DnsRecord>().unwrap(), read);
+    }
+
+    #[test]
+    fn unknown_types_are_written_back_unchanged() {
+        let record = DnsRecord::read(&mut BytePacketBuffer::from_bytes(PRIVATE_USE)).unwrap();
+
+        assert_eq!(record.qtype(), QueryType::UNKNOWN(65280));
+        assert_eq!(record.class(), QueryClass::IN);
+        assert_eq!(record.ttl(), 300);
+
+        let mut buffer = BytePacketBuffer::new();
+        record.write(&mut buffer).unwrap();
+        assert_eq!(buffer.written(), PRIVATE_USE);
+    }
+
+    #[test]
+    fn unknown_types_use_the_generic_text_form() {
+        let record = DnsRecord::read(&mut BytePacketBuffer::from_bytes(PRIVATE_USE)).unwrap();
+        assert_eq!(record.to_string(), "example.com. 300 IN TYPE65280 \\# 4 DEADBEEF");
+
+        // The hex digits may be given in either case and split into groups.
+        let parsed: DnsRecord = "example.com. 300 IN TYPE65280 \\# 4 dead beef".parse().unwrap();
+        assert_eq!(parsed, record);
+
+        let empty: DnsRecord = "example.com. IN TYPE65280 \\# 0".parse().unwrap();
+        assert_eq!(empty.to_string(), "example.com. 0 IN TYPE65280 \\# 0");
+
+        for invalid in ["\\# 3 DEADBEEF", "\\# 4 DEADBEEG", "\\# DEADBEEF", "\\#"] {
+            let text = format!("example.com. 300 IN TYPE65280 {}", invalid);
+            assert!(text.parse::<DnsRecord>().is_err(), "{}", text);
+        }
+    }
+
+    fn txt(txt: Vec<Vec<u8>>) -> DnsRecord {
+        DnsRecord::TXT { domain: "example.com".parse().unwrap(), txt, ttl: 300 }
+    }
+
+    fn write(record: &DnsRecord) -> Vec<u8> {
+        let mut buffer = BytePacketBuffer::with_capacity(MAX_MESSAGE_SIZE);
+        record.write(&mut buffer).unwrap();
+
+        buffer.written().to_vec()
+    }
+
+    #[test]
+    fn long_txt_strings_are_split() {
+        let value = vec![b'x'; 600];
+        let record = txt(vec![value.clone()]);
+
+        let rdata = record.rdata().unwrap();
+        assert_eq!(rdata.len(), 603);
+        assert_eq!((rdata[0], rdata[256], rdata[512]), (255, 255, 90));
+
+        let read = DnsRecord::read(&mut BytePacketBuffer::from_bytes(&write(&record))).unwrap();
+        assert_eq!(read, txt(vec![vec![b'x'; 255], vec![b'x'; 255], vec![b'x'; 90]]));
+        assert_eq!(read.txt_string(), Some(String::from_utf8(value).unwrap()));
+    }
+
+    #[test]
+    fn txt_records_hold_several_strings() {
+        let record = txt(vec![b"v=spf1 ".to_vec(), b"".to_vec(), b"-all".to_vec()]);
+        assert_eq!(record.rdata().unwrap(), b"\x07v=spf1 \x00\x04-all");
+
+        let read = DnsRecord::read(&mut BytePacketBuffer::from_bytes(&write(&record))).unwrap();
+        assert_eq!(read, record);
+        assert_eq!(read.txt_string().as_deref(), Some("v=spf1 -all"));
+        assert_eq!(read.to_string(), "example.com. 300 IN TXT \"v=spf1 \" \"\" \"-all\"");
+    }
+
+    #[test]
+    fn empty_txt_records_hold_a_single_empty_string() {
+        let record = txt(Vec::new());
+        assert_eq!(record.rdata().unwrap(), b"\x00");
+        assert_eq!(record.to_string(), "example.com. 300 IN TXT \"\"");
+
+        let read = DnsRecord::read(&mut BytePacketBuffer::from_bytes(&write(&record))).unwrap();
+        assert_eq!(read, txt(vec![Vec::new()]));
+        assert_eq!(read.txt_string().as_deref(), Some(""));
+    }
+}
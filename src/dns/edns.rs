@@ -0,0 +1,231 @@
+use crate::dns::byte_packet_buffer::BytePacketBuffer;
+use crate::dns::byte_packet_buffer_error::BytePacketBufferError;
+use crate::dns::name::Name;
+use crate::dns::query_type::QueryType;
+
+/// The UDP payload size advertised when making use of EDNS(0). 1232 bytes
+/// avoids IP fragmentation on practically every path, both for IPv4 and IPv6.
+///
+/// https://www.dnsflagday.net/2020/
+pub const DEFAULT_UDP_PAYLOAD_SIZE: u16 = 1232;
+
+/// RFC 6891
+/// 6.1.2. Wire Format
+///
+/// The OPT pseudo-RR carries the EDNS(0) information of a message. It is
+/// placed in the additional section, but unlike every other record it says
+/// nothing about a domain, and instead repurposes the fixed record fields:
+///
+///     +------------+--------------+------------------------------+
+///     | Field Name | Field Type   | Description                  |
+///     +------------+--------------+------------------------------+
+///     | NAME       | domain name  | MUST be 0 (root domain)      |
+///     | TYPE       | u_int16_t    | OPT (41)                     |
+///     | CLASS      | u_int16_t    | requestor's UDP payload size |
+///     | TTL        | u_int32_t    | extended RCODE and flags     |
+///     | RDLEN      | u_int16_t    | length of all RDATA          |
+///     | RDATA      | octet stream | {attribute,value} pairs      |
+///     +------------+--------------+------------------------------+
+///
+/// The TTL field is split up as follows:
+///
+///                 +0 (MSB)                            +1 (LSB)
+///      +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///   0: |         EXTENDED-RCODE        |            VERSION            |
+///      +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///   2: | DO|                           Z                               |
+///      +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Edns {
+    /// The number of octets of the largest UDP payload that can be reassembled
+    /// and delivered in the sender's network stack.
+    pub udp_payload_size: u16,
+
+    /// The upper eight bits of the extended 12-bit RCODE. The lower four bits
+    /// are held within the message header. `DnsPacket` combines both into the
+    /// `rescode` of its header on read, and sets this from it on write.
+    pub extended_rcode: u8,
+
+    /// The version of the implementation, currently always zero.
+    pub version: u8,
+
+    /// DNSSEC OK bit, indicating the resolver is able to accept DNSSEC
+    /// security records.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc3225#section-3
+    pub dnssec_ok: bool,
+
+    /// Any number of options, each describing an extension of the protocol.
+    pub options: Vec<EdnsOption>,
+}
+
+/// RFC 6891
+/// 6.1.2. Wire Format
+///
+/// Each option within the RDATA of the OPT record is encoded as:
+///
+///                +0 (MSB)                            +1 (LSB)
+///     +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///  0: |                          OPTION-CODE                          |
+///     +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///  2: |                         OPTION-LENGTH                         |
+///     +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///  4: |                                                               |
+///     /                          OPTION-DATA                          /
+///     /                                                               /
+///     +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EdnsOption {
+    pub code: u16,
+    pub data: Vec<u8>,
+}
+
+impl Edns {
+    pub fn new(udp_payload_size: u16) -> Edns {
+        Edns {
+            udp_payload_size,
+            extended_rcode: 0,
+            version: 0,
+            dnssec_ok: false,
+            options: Vec::new(),
+        }
+    }
+
+    /// Build the EDNS information from the fields of an OPT record, where the
+    /// name, type, class, ttl and data length have already been read from the
+    /// buffer and the buffer is positioned at the start of the RDATA.
+    pub fn read(buffer: &mut BytePacketBuffer, class: u16, ttl: u32, data_len: u16) -> Result<Edns, BytePacketBufferError> {
+        let mut edns = Edns::new(class);
+
+        edns.extended_rcode = ((ttl >> 24) & 0xFF) as u8;
+        edns.version = ((ttl >> 16) & 0xFF) as u8;
+        edns.dnssec_ok = (ttl & (1 << 15)) > 0;
+
+        let end = buffer.position() + data_len as usize;
+
+        while buffer.position() < end {
+            let code = buffer.read_u16()?;
+            let length = buffer.read_u16()?;
+
+            let mut data = Vec::with_capacity(length as usize);
+            for _ in 0..length {
+                data.push(buffer.read()?);
+            }
+
+            edns.options.push(EdnsOption { code, data });
+        }
+
+        Ok(edns)
+    }
+
+    /// The TTL field of the OPT record, which holds the extended RCODE, the
+    /// version and the flags.
+    pub fn ttl(&self) -> u32 {
+        ((self.extended_rcode as u32) << 24)
+            | ((self.version as u32) << 16)
+            | ((self.dnssec_ok as u32) << 15)
+    }
+
+    /// The RDATA of the OPT record, holding every option as its code, length
+    /// and data.
+    pub fn data(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+
+        for option in &self.options {
+            data.extend_from_slice(&option.code.to_be_bytes());
+            data.extend_from_slice(&(option.data.len() as u16).to_be_bytes());
+            data.extend_from_slice(&option.data);
+        }
+
+        data
+    }
+
+    /// The length of the OPT record when written: the root name, the fixed
+    /// record fields and the options.
+    pub fn wire_length(&self) -> usize {
+        11 + self.data().len()
+    }
+
+    /// Write the EDNS information as an OPT record, owned by the root domain.
+    pub fn write(&self, buffer: &mut BytePacketBuffer) -> Result<(), BytePacketBufferError> {
+        buffer.write_question_name(&Name::root())?;
+        buffer.write_u16(QueryType::OPT.to_num())?;
+        buffer.write_u16(self.udp_payload_size)?;
+        buffer.write_u32(self.ttl())?;
+
+        let data = self.data();
+        buffer.write_u16(data.len() as u16)?;
+
+        for x in &data {
+            buffer.write_u8(*x)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dns::byte_packet_buffer::{BytePacketBuffer, MAX_MESSAGE_SIZE};
+    use crate::dns::dns_packet::DnsPacket;
+    use crate::dns::dns_record::DnsRecord;
+    use crate::dns::edns::{Edns, EdnsOption};
+    use crate::dns::result_code::ResultCode;
+
+    // An OPT record advertising 1232 bytes, with the extended RCODE bits of
+    // BADVERS, version 0, the DO flag and a cookie option.
+    const OPT: &[u8] = b"\x00\x00\x29\x04\xD0\x01\x00\x80\x00\x00\x0C\x00\x0A\x00\x08\x01\x02\x03\x04\x05\x06\x07\x08";
+
+    fn edns() -> Edns {
+        Edns {
+            udp_payload_size: 1232,
+            extended_rcode: 1,
+            version: 0,
+            dnssec_ok: true,
+            options: vec![EdnsOption { code: 10, data: vec![1, 2, 3, 4, 5, 6, 7, 8] }],
+        }
+    }
+
+    #[test]
+    fn opt_records_are_written() {
+        let mut buffer = BytePacketBuffer::new();
+        edns().write(&mut buffer).unwrap();
+
+        assert_eq!(buffer.written(), OPT);
+        assert_eq!(edns().wire_length(), OPT.len());
+    }
+
+    #[test]
+    fn opt_records_are_read() {
+        let record = DnsRecord::read(&mut BytePacketBuffer::from_bytes(OPT)).unwrap();
+        assert_eq!(record, DnsRecord::OPT { edns: edns() });
+
+        let mut edns = Edns::new(4096);
+        edns.version = 1;
+
+        let mut buffer = BytePacketBuffer::new();
+        edns.write(&mut buffer).unwrap();
+
+        let record = DnsRecord::read(&mut BytePacketBuffer::from_bytes(buffer.written())).unwrap();
+        assert_eq!(record, DnsRecord::OPT { edns });
+    }
+
+    #[test]
+    fn extended_rcodes_are_split_between_header_and_opt() {
+        let mut packet = DnsPacket::new();
+        packet.header.rescode = ResultCode::BADVERS;
+        packet.edns = Some(Edns::new(1232));
+
+        let mut buffer = BytePacketBuffer::new();
+        packet.write(&mut buffer, MAX_MESSAGE_SIZE).unwrap();
+        let message = buffer.written();
+
+        // BADVERS is 16, which leaves nothing for the header to hold.
+        assert_eq!(message[3] & 0x0F, 0);
+        assert_eq!(message[12 + 5], 1);
+
+        let read = DnsPacket::from_buffer(&mut BytePacketBuffer::from_bytes(message)).unwrap();
+        assert_eq!(read.header.rescode, ResultCode::BADVERS);
+        assert_eq!(read.edns.map(|edns| edns.extended_rcode), Some(1));
+    }
+}
@@ -0,0 +1,549 @@
+//! RFC 8427
+//! Representing DNS Messages in JSON
+//!
+//! Messages, headers, questions and records are serialized as JSON objects
+//! using the member names of RFC 8427. Every record carries its data both as
+//! hexadecimal (`RDATAHEX`) and, for the known types, in its presentation
+//! format under a member named after the type, such as `rdataMX`. Either is
+//! accepted when deserializing, with `RDATAHEX` taking precedence.
+//!
+//! The OPT pseudo-record is included within `additionalRRs`, as it is found
+//! on the wire.
+//!
+//! https://datatracker.ietf.org/doc/html/rfc8427
+
+use std::collections::BTreeMap;
+use serde::de::Error as _;
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::dns::byte_packet_buffer::{BytePacketBuffer, MAX_MESSAGE_SIZE};
+use crate::dns::dns_header::DnsHeader;
+use crate::dns::dns_packet::DnsPacket;
+use crate::dns::dns_question::DnsQuestion;
+use crate::dns::dns_record::DnsRecord;
+use crate::dns::name::Name;
+use crate::dns::opcode::Opcode;
+use crate::dns::presentation_error::PresentationError;
+use crate::dns::query_class::QueryClass;
+use crate::dns::query_type::QueryType;
+use crate::dns::result_code::ResultCode;
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct HeaderJson {
+    #[serde(rename = "ID")]
+    id: u16,
+    #[serde(rename = "QR")]
+    qr: u8,
+    #[serde(rename = "Opcode")]
+    opcode: u8,
+    #[serde(rename = "AA")]
+    aa: u8,
+    #[serde(rename = "TC")]
+    tc: u8,
+    #[serde(rename = "RD")]
+    rd: u8,
+    #[serde(rename = "RA")]
+    ra: u8,
+    #[serde(rename = "AD")]
+    ad: u8,
+    #[serde(rename = "CD")]
+    cd: u8,
+    #[serde(rename = "RCODE")]
+    rcode: u16,
+    #[serde(rename = "QDCOUNT")]
+    qdcount: u16,
+    #[serde(rename = "ANCOUNT")]
+    ancount: u16,
+    #[serde(rename = "NSCOUNT")]
+    nscount: u16,
+    #[serde(rename = "ARCOUNT")]
+    arcount: u16,
+}
+
+impl From<&DnsHeader> for HeaderJson {
+    fn from(header: &DnsHeader) -> Self {
+        HeaderJson {
+            id: header.id,
+            qr: header.response as u8,
+            opcode: header.opcode.to_num(),
+            aa: header.authoritative_answer as u8,
+            tc: header.truncated_message as u8,
+            rd: header.recursion_desired as u8,
+            ra: header.recursion_available as u8,
+            ad: header.authed_data as u8,
+            cd: header.checking_disabled as u8,
+            rcode: header.rescode.to_num(),
+            qdcount: header.questions,
+            ancount: header.answers,
+            nscount: header.authoritative_entries,
+            arcount: header.resource_entries,
+        }
+    }
+}
+
+impl From<HeaderJson> for DnsHeader {
+    fn from(json: HeaderJson) -> Self {
+        let mut header = DnsHeader::new();
+
+        header.id = json.id;
+        header.response = json.qr > 0;
+        header.opcode = Opcode::from_num(json.opcode);
+        header.authoritative_answer = json.aa > 0;
+        header.truncated_message = json.tc > 0;
+        header.recursion_desired = json.rd > 0;
+        header.recursion_available = json.ra > 0;
+        header.authed_data = json.ad > 0;
+        header.checking_disabled = json.cd > 0;
+        header.rescode = ResultCode::from_num(json.rcode);
+        header.questions = json.qdcount;
+        header.answers = json.ancount;
+        header.authoritative_entries = json.nscount;
+        header.resource_entries = json.arcount;
+
+        header
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct QuestionJson {
+    #[serde(rename = "NAME")]
+    name: String,
+    #[serde(rename = "TYPE", default, skip_serializing_if = "Option::is_none")]
+    qtype: Option<u16>,
+    #[serde(rename = "TYPEname", default, skip_serializing_if = "Option::is_none")]
+    qtype_name: Option<String>,
+    #[serde(rename = "CLASS", default, skip_serializing_if = "Option::is_none")]
+    class: Option<u16>,
+    #[serde(rename = "CLASSname", default, skip_serializing_if = "Option::is_none")]
+    class_name: Option<String>,
+}
+
+impl From<&DnsQuestion> for QuestionJson {
+    fn from(question: &DnsQuestion) -> Self {
+        QuestionJson {
+            name: question.q_name.to_fqdn(),
+            qtype: Some(question.q_type.to_num()),
+            qtype_name: Some(question.q_type.to_string()),
+            class: Some(question.q_class.to_num()),
+            class_name: Some(question.q_class.to_string()),
+        }
+    }
+}
+
+impl TryFrom<QuestionJson> for DnsQuestion {
+    type Error = PresentationError;
+
+    fn try_from(json: QuestionJson) -> Result<Self, Self::Error> {
+        let q_type = resolve_type(json.qtype, json.qtype_name.as_deref())?;
+        let q_class = resolve_class(json.class, json.class_name.as_deref())?;
+
+        Ok(DnsQuestion::new(json.name.parse()?, q_type, q_class))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordJson {
+    #[serde(rename = "NAME")]
+    name: String,
+    #[serde(rename = "TYPE", default, skip_serializing_if = "Option::is_none")]
+    qtype: Option<u16>,
+    #[serde(rename = "TYPEname", default, skip_serializing_if = "Option::is_none")]
+    qtype_name: Option<String>,
+    #[serde(rename = "CLASS", default, skip_serializing_if = "Option::is_none")]
+    class: Option<u16>,
+    #[serde(rename = "CLASSname", default, skip_serializing_if = "Option::is_none")]
+    class_name: Option<String>,
+    #[serde(rename = "TTL", default)]
+    ttl: u32,
+    #[serde(rename = "RDLENGTH", default, skip_serializing_if = "Option::is_none")]
+    rdlength: Option<u16>,
+    #[serde(rename = "RDATAHEX", default, skip_serializing_if = "Option::is_none")]
+    rdata_hex: Option<String>,
+    /// The data in presentation format, under a member named after the type
+    /// such as `rdataA`. Other unknown members are kept here as well.
+    #[serde(flatten)]
+    rdata: BTreeMap<String, serde_json::Value>,
+}
+
+impl TryFrom<&DnsRecord> for RecordJson {
+    type Error = String;
+
+    fn try_from(record: &DnsRecord) -> Result<Self, Self::Error> {
+        let data = record.rdata().map_err(|error| error.to_string())?;
+
+        // Only the known types have a presentation format of their own.
+        let mut rdata = BTreeMap::new();
+        if !matches!(record, DnsRecord::UNHANDLED { .. } | DnsRecord::OPT { .. }) {
+            rdata.insert(format!("rdata{}", record.qtype()), serde_json::Value::String(record.rdata_string()));
+        }
+
+        Ok(RecordJson {
+            name: record.domain().map_or(".".to_string(), |domain| domain.to_fqdn()),
+            qtype: Some(record.qtype().to_num()),
+            qtype_name: Some(record.qtype().to_string()),
+            class: Some(record.class().to_num()),
+            class_name: Some(record.class().to_string()),
+            ttl: record.ttl(),
+            rdlength: Some(data.len() as u16),
+            rdata_hex: Some(to_hex(&data)),
+            rdata,
+        })
+    }
+}
+
+impl TryFrom<RecordJson> for DnsRecord {
+    type Error = PresentationError;
+
+    fn try_from(json: RecordJson) -> Result<Self, Self::Error> {
+        let name: Name = json.name.parse()?;
+        let qtype = resolve_type(json.qtype, json.qtype_name.as_deref())?;
+        let class = resolve_class(json.class, json.class_name.as_deref())?;
+
+        // The record is put back together as a line of a master file, which
+        // takes care of every type and class in the same way as text does.
+        let rdata = match (&json.rdata_hex, json.rdata.get(&format!("rdata{}", qtype))) {
+            (Some(hex), _) => format!("\\# {} {}", hex.len() / 2, hex),
+            (None, Some(serde_json::Value::String(text))) => text.clone(),
+            _ => return Err(PresentationError::InvalidRecord(json.name)),
+        };
+
+        format!("{} {} {} {} {}", name.to_fqdn(), json.ttl, class, qtype, rdata).parse()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct MessageJson {
+    #[serde(flatten)]
+    header: HeaderJson,
+    /// A message with a single question may give it directly within the
+    /// message, rather than within `questionRRs`.
+    #[serde(rename = "QNAME", default, skip_serializing)]
+    qname: Option<String>,
+    #[serde(rename = "QTYPE", default, skip_serializing)]
+    qtype: Option<u16>,
+    #[serde(rename = "QTYPEname", default, skip_serializing)]
+    qtype_name: Option<String>,
+    #[serde(rename = "QCLASS", default, skip_serializing)]
+    qclass: Option<u16>,
+    #[serde(rename = "QCLASSname", default, skip_serializing)]
+    qclass_name: Option<String>,
+    #[serde(rename = "questionRRs", default)]
+    questions: Vec<QuestionJson>,
+    #[serde(rename = "answerRRs", default)]
+    answers: Vec<RecordJson>,
+    #[serde(rename = "authorityRRs", default)]
+    authorities: Vec<RecordJson>,
+    #[serde(rename = "additionalRRs", default)]
+    resources: Vec<RecordJson>,
+    #[serde(rename = "messageOctetsHEX", default, skip_serializing_if = "Option::is_none")]
+    message_octets: Option<String>,
+}
+
+impl MessageJson {
+    fn from_packet(packet: &DnsPacket, include_octets: bool) -> Result<MessageJson, String> {
+        // The packet is written out and read back in to settle the counts of
+        // the header and the extended RCODE within the OPT record, exactly as
+        // it would be sent.
+        let mut buffer = BytePacketBuffer::with_capacity(MAX_MESSAGE_SIZE);
+        packet.write(&mut buffer, MAX_MESSAGE_SIZE).map_err(|error| error.to_string())?;
+
+        let message = buffer.written();
+        let packet = DnsPacket::from_buffer(&mut BytePacketBuffer::from_bytes(message)).map_err(|error| error.to_string())?;
+
+        let records = |records: &[DnsRecord]| -> Result<Vec<RecordJson>, String> {
+            records.iter().map(RecordJson::try_from).collect()
+        };
+
+        let mut resources = records(&packet.resources)?;
+        if let Some(edns) = &packet.edns {
+            resources.push(RecordJson::try_from(&DnsRecord::OPT { edns: edns.clone() })?);
+        }
+
+        Ok(MessageJson {
+            header: HeaderJson::from(&packet.header),
+            qname: None,
+            qtype: None,
+            qtype_name: None,
+            qclass: None,
+            qclass_name: None,
+            questions: packet.questions.iter().map(QuestionJson::from).collect(),
+            answers: records(&packet.answers)?,
+            authorities: records(&packet.authorities)?,
+            resources,
+            message_octets: include_octets.then(|| to_hex(message)),
+        })
+    }
+
+    fn into_packet(self) -> Result<DnsPacket, PresentationError> {
+        let mut packet = DnsPacket::new();
+        packet.header = DnsHeader::from(self.header);
+
+        if let Some(qname) = self.qname {
+            packet.questions.push(DnsQuestion::try_from(QuestionJson {
+                name: qname,
+                qtype: self.qtype,
+                qtype_name: self.qtype_name,
+                class: self.qclass,
+                class_name: self.qclass_name,
+            })?);
+        }
+
+        for question in self.questions {
+            packet.questions.push(DnsQuestion::try_from(question)?);
+        }
+        for record in self.answers {
+            packet.answers.push(DnsRecord::try_from(record)?);
+        }
+        for record in self.authorities {
+            packet.authorities.push(DnsRecord::try_from(record)?);
+        }
+        for record in self.resources {
+            // The OPT pseudo-record is lifted out of the additional section,
+            // just as it is when a packet is read from the wire.
+            match DnsRecord::try_from(record)? {
+                DnsRecord::OPT { edns } => packet.edns = Some(edns),
+                record => packet.resources.push(record),
+            }
+        }
+
+        Ok(packet)
+    }
+}
+
+impl DnsPacket {
+    /// Serialize the packet as RFC 8427 JSON, optionally including the whole
+    /// message as it is written to the wire in `messageOctetsHEX`.
+    #[allow(dead_code)]
+    pub fn to_json(&self, include_octets: bool) -> Result<String, serde_json::Error> {
+        let json = MessageJson::from_packet(self, include_octets).map_err(<serde_json::Error as serde::ser::Error>::custom)?;
+        serde_json::to_string(&json)
+    }
+
+    /// Deserialize a packet from RFC 8427 JSON.
+    #[allow(dead_code)]
+    pub fn from_json(json: &str) -> Result<DnsPacket, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+impl Serialize for DnsPacket {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MessageJson::from_packet(self, false).map_err(S::Error::custom)?.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DnsPacket {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        MessageJson::deserialize(deserializer)?.into_packet().map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for DnsHeader {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        HeaderJson::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DnsHeader {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(DnsHeader::from(HeaderJson::deserialize(deserializer)?))
+    }
+}
+
+impl Serialize for DnsQuestion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        QuestionJson::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DnsQuestion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        DnsQuestion::try_from(QuestionJson::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for DnsRecord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        RecordJson::try_from(self).map_err(S::Error::custom)?.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DnsRecord {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        DnsRecord::try_from(RecordJson::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+// The type is taken from its number when given, otherwise from its name.
+fn resolve_type(num: Option<u16>, name: Option<&str>) -> Result<QueryType, PresentationError> {
+    match (num, name) {
+        (Some(num), _) => Ok(QueryType::from_num(num)),
+        (None, Some(name)) => name.parse(),
+        (None, None) => Err(PresentationError::UnknownType(String::new())),
+    }
+}
+
+// The class is taken from its number when given, otherwise from its name,
+// defaulting to IN when neither is given.
+fn resolve_class(num: Option<u16>, name: Option<&str>) -> Result<QueryClass, PresentationError> {
+    match (num, name) {
+        (Some(num), _) => Ok(QueryClass::from_num(num)),
+        (None, Some(name)) => name.parse(),
+        (None, None) => Ok(QueryClass::IN),
+    }
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|x| format!("{:02X}", x)).collect()
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use crate::dns::byte_packet_buffer::{BytePacketBuffer, MAX_MESSAGE_SIZE};
+    use crate::dns::dns_packet::DnsPacket;
+    use crate::dns::dns_question::DnsQuestion;
+    use crate::dns::dns_record::DnsRecord;
+    use crate::dns::edns::{Edns, EdnsOption};
+    use crate::dns::json::to_hex;
+    use crate::dns::name::Name;
+    use crate::dns::query_class::QueryClass;
+    use crate::dns::query_type::QueryType;
+    use crate::dns::result_code::ResultCode;
+
+    fn name(name: &str) -> Name {
+        name.parse().unwrap()
+    }
+
+    // A response holding every kind of record, including ones of an unknown
+    // type and class, with an extended RCODE carried by the OPT record.
+    fn packet() -> DnsPacket {
+        let mut packet = DnsPacket::new();
+        packet.header.id = 0xBEEF;
+        packet.header.response = true;
+        packet.header.recursion_desired = true;
+        packet.header.rescode = ResultCode::BADCOOKIE;
+        packet.questions.push(DnsQuestion::new(name("example.com"), QueryType::A, QueryClass::IN));
+
+        packet.answers = vec![
+            DnsRecord::A { domain: name("example.com"), addr: Ipv4Addr::new(10, 0, 0, 1), ttl: 300 },
+            DnsRecord::AAAA { domain: name("example.com"), addr: Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), ttl: 300 },
+            DnsRecord::CNAME { domain: name("www.example.com"), host: name("example.com"), ttl: 300 },
+            DnsRecord::MX { domain: name("example.com"), preference: 10, host: name("mail.example.com"), ttl: 300 },
+            DnsRecord::PTR { domain: name("1.0.0.10.in-addr.arpa"), host: name("example.com"), ttl: 300 },
+            DnsRecord::SRV { domain: name("_imaps._tcp.example.com"), priority: 1, weight: 5, port: 993, host: name("mail.example.com"), ttl: 300 },
+            DnsRecord::TXT { domain: name("example.com"), txt: vec![b"v=spf1 ".to_vec(), b"-all".to_vec(), b"\"quoted\" \xFF".to_vec()], ttl: 300 },
+            DnsRecord::UNHANDLED { domain: name("example.com"), qtype: QueryType::UNKNOWN(65280), class: QueryClass::IN, data: vec![0xDE, 0xAD, 0xBE, 0xEF], ttl: 300 },
+            DnsRecord::UNHANDLED { domain: name("version.bind"), qtype: QueryType::TXT, class: QueryClass::CH, data: b"\x01x".to_vec(), ttl: 0 },
+        ];
+        packet.authorities = vec![
+            DnsRecord::NS { domain: name("example.com"), host: name("ns1.example.com"), ttl: 3600 },
+            DnsRecord::SOA {
+                domain: name("example.com"),
+                mname: name("ns1.example.com"),
+                rname: name("hostmaster.example.com"),
+                serial: 2024010101,
+                refresh: 7200,
+                retry: 900,
+                expire: 1209600,
+                minimum: 300,
+                ttl: 3600,
+            },
+        ];
+        packet.resources.push(DnsRecord::A { domain: name("ns1.example.com"), addr: Ipv4Addr::new(10, 0, 0, 53), ttl: 3600 });
+
+        let mut edns = Edns::new(1232);
+        edns.dnssec_ok = true;
+        edns.options.push(EdnsOption { code: 10, data: vec![1, 2, 3, 4, 5, 6, 7, 8] });
+        packet.edns = Some(edns);
+
+        packet
+    }
+
+    fn wire(packet: &DnsPacket) -> Vec<u8> {
+        let mut buffer = BytePacketBuffer::with_capacity(MAX_MESSAGE_SIZE);
+        packet.write(&mut buffer, MAX_MESSAGE_SIZE).unwrap();
+
+        buffer.written().to_vec()
+    }
+
+    #[test]
+    fn every_record_round_trips() {
+        // The packet as it is read from the wire, with the upper bits of the
+        // RCODE settled within the OPT record.
+        let packet = DnsPacket::from_buffer(&mut BytePacketBuffer::from_bytes(&wire(&packet()))).unwrap();
+
+        for include_octets in [false, true] {
+            let read = DnsPacket::from_json(&packet.to_json(include_octets).unwrap()).unwrap();
+
+            assert_eq!(read.header.id, packet.header.id);
+            assert_eq!(read.header.rescode, packet.header.rescode);
+            assert_eq!(read.questions, packet.questions);
+            assert_eq!(read.answers, packet.answers);
+            assert_eq!(read.authorities, packet.authorities);
+            assert_eq!(read.resources, packet.resources);
+            assert_eq!(read.edns, packet.edns);
+
+            // Nothing is lost, so the message on the wire is the same too.
+            assert_eq!(wire(&read), wire(&packet));
+        }
+    }
+
+    #[test]
+    fn members_are_named_as_in_rfc_8427() {
+        let packet = packet();
+        let json: serde_json::Value = serde_json::from_str(&packet.to_json(true).unwrap()).unwrap();
+
+        assert_eq!(json["ID"], 0xBEEF);
+        assert_eq!(json["QR"], 1);
+        assert_eq!(json["RD"], 1);
+        assert_eq!(json["RCODE"], 23);
+        assert_eq!(json["QDCOUNT"], 1);
+        assert_eq!(json["ANCOUNT"], 9);
+        assert_eq!(json["NSCOUNT"], 2);
+        assert_eq!(json["ARCOUNT"], 2);
+
+        assert_eq!(json["questionRRs"][0]["NAME"], "example.com.");
+        assert_eq!(json["questionRRs"][0]["TYPEname"], "A");
+        assert_eq!(json["answerRRs"][0]["rdataA"], "10.0.0.1");
+        assert_eq!(json["answerRRs"][0]["RDATAHEX"], "0A000001");
+        assert_eq!(json["answerRRs"][7]["TYPEname"], "TYPE65280");
+        assert_eq!(json["answerRRs"][7].get("rdataTYPE65280"), None);
+        assert_eq!(json["additionalRRs"][1]["TYPEname"], "OPT");
+        assert_eq!(json["messageOctetsHEX"], to_hex(&wire(&packet)));
+
+        let json: serde_json::Value = serde_json::from_str(&packet.to_json(false).unwrap()).unwrap();
+        assert_eq!(json.get("messageOctetsHEX"), None);
+    }
+
+    #[test]
+    fn messages_are_read_from_their_presentation_format() {
+        // A query given with the question inlined, and an answer with only
+        // the presentation format of its data.
+        let json = r#"{
+            "ID": 19678, "QR": 1, "Opcode": 0, "RD": 1, "RCODE": 0,
+            "QNAME": "example.com", "QTYPEname": "A",
+            "answerRRs": [{ "NAME": "example.com.", "TYPE": 1, "TTL": 300, "rdataA": "192.0.2.1" }]
+        }"#;
+
+        let packet = DnsPacket::from_json(json).unwrap();
+
+        assert_eq!(packet.questions, vec![DnsQuestion::new(name("example.com"), QueryType::A, QueryClass::IN)]);
+        assert_eq!(packet.answers, vec![DnsRecord::A { domain: name("example.com"), addr: Ipv4Addr::new(192, 0, 2, 1), ttl: 300 }]);
+    }
+
+    #[test]
+    fn invalid_rdata_hex_is_rejected() {
+        // An odd number of digits, a digit which isn't one, and data of the
+        // wrong length for the type.
+        for hex in ["0A00000", "0A0000GG", "0A00"] {
+            let json = format!(r#"{{ "answerRRs": [{{ "NAME": "example.com.", "TYPE": 1, "RDATAHEX": "{}" }}] }}"#, hex);
+            assert!(DnsPacket::from_json(&json).is_err(), "{}", hex);
+        }
+
+        // The hex form takes precedence over the presentation format.
+        let json = r#"{ "answerRRs": [{ "NAME": "example.com.", "TYPE": 1, "RDATAHEX": "0A00", "rdataA": "10.0.0.1" }] }"#;
+        assert!(DnsPacket::from_json(json).is_err());
+    }
+}
@@ -0,0 +1,502 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use rand::Rng;
+use crate::dns::presentation_error::PresentationError;
+
+/// The maximum length of a single label.
+///
+/// RFC 1035
+/// 2.3.4. Size limits
+pub const MAX_LABEL_LENGTH: usize = 63;
+
+/// The maximum length of a name on the wire, including the length octets of
+/// every label and the terminating zero length octet of the root.
+///
+/// RFC 1035
+/// 2.3.4. Size limits
+pub const MAX_NAME_LENGTH: usize = 255;
+
+/// RFC 1035
+/// 3.1. Name space definitions
+///
+/// Domain names in messages are expressed in terms of a sequence of labels,
+/// ordered from the most specific to the least specific, with the root being
+/// represented by the empty sequence. For example `www.example.com` is held as
+/// the labels `www`, `example` and `com`.
+///
+/// Labels are kept as raw octets, as a label may hold any value on the wire,
+/// including dots, spaces and bytes which are not valid UTF-8.
+///
+/// Names are compared case-insensitively, as required by RFC 4343, while the
+/// original case of every label is kept. Ordering follows the canonical order
+/// of RFC 4034 6.1, which sorts names by their labels from the right.
+#[derive(Clone, Debug, Default)]
+pub struct Name {
+    labels: Vec<Vec<u8>>,
+}
+
+impl Name {
+    /// The root of the domain name space, which has no labels at all.
+    pub fn root() -> Name {
+        Name { labels: Vec::new() }
+    }
+
+    /// Create a name from its labels, ordered from the most specific to the
+    /// least specific. The labels are not validated, which is left to the
+    /// point the name is written.
+    pub fn from_labels(labels: Vec<Vec<u8>>) -> Name {
+        Name { labels }
+    }
+
+    /// Parse an internationalized name, such as `bücher.example`, converting
+    /// it to A-labels following UTS #46, giving `xn--bcher-kva.example`.
+    /// Names which are already ASCII are parsed as they are by `FromStr`.
+    ///
+    /// https://www.unicode.org/reports/tr46/#ToASCII
+    #[cfg(feature = "idna")]
+    pub fn from_unicode(s: &str) -> Result<Name, PresentationError> {
+        if s.is_ascii() {
+            return s.parse();
+        }
+
+        idna::domain_to_ascii(s)
+            .map_err(|_| PresentationError::InvalidIdna(s.to_string()))?
+            .parse()
+    }
+
+    pub fn labels(&self) -> &[Vec<u8>] {
+        &self.labels
+    }
+
+    /// The number of labels within the name, not counting the root.
+    pub fn label_count(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// The length of the name when written to the wire without compression.
+    pub fn wire_length(&self) -> usize {
+        self.labels.iter().map(|label| label.len() + 1).sum::<usize>() + 1
+    }
+
+    /// The name as a fully qualified domain name, ending with a dot, as it is
+    /// written within master files. The root is a single dot.
+    pub fn to_fqdn(&self) -> String {
+        if self.is_root() {
+            return ".".to_string();
+        }
+
+        format!("{}.", self)
+    }
+
+    /// The name as it is shown to users, with every A-label converted back to
+    /// the Unicode label it encodes following UTS #46, for example
+    /// `bücher.example` for `xn--bcher-kva.example`. Labels which are not
+    /// valid A-labels are escaped as in the regular presentation form, and as
+    /// with `Display` there is no trailing dot.
+    #[cfg(feature = "idna")]
+    pub fn to_unicode(&self) -> String {
+        if self.is_root() {
+            return ".".to_string();
+        }
+
+        self.labels.iter()
+            .map(|label| to_u_label(label).unwrap_or_else(|| EscapedLabel(label).to_string()))
+            .collect::<Vec<String>>()
+            .join(".")
+    }
+
+    /// A copy of the name with the case of every letter flipped at random,
+    /// which is still the same name but adds a bit of entropy per letter to a
+    /// query that an attacker has to guess.
+    ///
+    /// https://datatracker.ietf.org/doc/html/draft-vixie-dnsext-dns0x20-00
+    pub fn with_random_case<R: Rng>(&self, rng: &mut R) -> Name {
+        let labels = self.labels.iter()
+            .map(|label| label.iter()
+                .map(|x| if x.is_ascii_alphabetic() && rng.gen() { *x ^ 0x20 } else { *x })
+                .collect())
+            .collect();
+
+        Name { labels }
+    }
+
+    /// Whether the names are equal including the case of every letter, unlike
+    /// `==` which ignores case.
+    pub fn eq_case_sensitive(&self, other: &Name) -> bool {
+        self.labels == other.labels
+    }
+
+    /// The name directly below this one with the given label, for example
+    /// `www.example.com` for the label `www` of `example.com`.
+    pub fn child(&self, label: &[u8]) -> Name {
+        let mut labels = Vec::with_capacity(self.labels.len() + 1);
+        labels.push(label.to_vec());
+        labels.extend(self.labels.iter().cloned());
+
+        Name { labels }
+    }
+
+    /// Iterate over this name and every name above it, ending with the root,
+    /// so the parent of a name is the second item. Reversing the iterator
+    /// walks from the root down to this name instead, one child at a time.
+    pub fn ancestors(&self) -> impl DoubleEndedIterator<Item=Name> + '_ {
+        (0..=self.labels.len()).map(move |index| Name { labels: self.labels[index..].to_vec() })
+    }
+
+    /// Whether this name is equal to or below the given name. Labels are
+    /// compared as a whole, so `example.com` is a subdomain of `com` and of
+    /// itself, but not of `ample.com`.
+    pub fn is_subdomain_of(&self, other: &Name) -> bool {
+        if other.labels.len() > self.labels.len() {
+            return false;
+        }
+
+        self.labels.iter().rev()
+            .zip(other.labels.iter().rev())
+            .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+}
+
+impl PartialEq for Name {
+    fn eq(&self, other: &Self) -> bool {
+        self.labels.len() == other.labels.len() && self.is_subdomain_of(other)
+    }
+}
+
+impl Eq for Name {}
+
+impl Hash for Name {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for label in &self.labels {
+            label.to_ascii_lowercase().hash(state);
+        }
+    }
+}
+
+/// RFC 4034
+/// 6.1. Canonical DNS Name Order
+///
+/// For the purposes of DNS security, owner names are ordered by treating
+/// individual labels as unsigned left-justified octet strings. The absence of
+/// a octet sorts before a zero value octet, and uppercase US-ASCII letters are
+/// treated as if they were lowercase US-ASCII letters.
+///
+/// To compute the canonical ordering of a set of DNS names, start by sorting
+/// the names according to their most significant (rightmost) labels. For names
+/// in which the most significant label is identical, continue sorting according
+/// to their next most significant label, and so forth.
+impl Ord for Name {
+    fn cmp(&self, other: &Self) -> Ordering {
+        for (a, b) in self.labels.iter().rev().zip(other.labels.iter().rev()) {
+            let a = a.iter().map(|x| x.to_ascii_lowercase());
+            let b = b.iter().map(|x| x.to_ascii_lowercase());
+
+            match a.cmp(b) {
+                Ordering::Equal => continue,
+                ordering => return ordering,
+            }
+        }
+
+        self.labels.len().cmp(&other.labels.len())
+    }
+}
+
+impl PartialOrd for Name {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// RFC 1035
+/// 5.1. Format
+///
+/// \X     where X is any character other than a digit (0-9), is used to quote
+///        that character so that its special meaning does not apply. For
+///        example, "\." can be used to place a dot character in a label.
+///
+/// \DDD   where each D is a digit is the octet corresponding to the decimal
+///        number described by DDD. The resulting octet is assumed to be text
+///        and is not checked for special meaning.
+///
+/// Characters with a special meaning in master files are quoted, while spaces,
+/// control characters and bytes outside of printable ASCII are written in the
+/// decimal form.
+struct EscapedLabel<'a>(&'a [u8]);
+
+impl Display for EscapedLabel<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for byte in self.0 {
+            match byte {
+                b'.' | b'\\' | b'"' | b'(' | b')' | b';' | b'@' | b'$' => write!(f, "\\{}", *byte as char)?,
+                0x21..=0x7E => write!(f, "{}", *byte as char)?,
+                _ => write!(f, "\\{:03}", byte)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Convert an A-label, one starting with `xn--`, to the U-label it encodes.
+/// Returns `None` for any other label, and for A-labels which do not decode
+/// to a valid U-label, so that those are shown as they are on the wire.
+///
+/// https://www.unicode.org/reports/tr46/#ToUnicode
+#[cfg(feature = "idna")]
+fn to_u_label(label: &[u8]) -> Option<String> {
+    let label = std::str::from_utf8(label).ok()?;
+    if !label.get(..4)?.eq_ignore_ascii_case("xn--") {
+        return None;
+    }
+
+    let (unicode, result) = idna::domain_to_unicode(label);
+    result.ok()?;
+
+    // A valid U-label never holds ASCII other than letters, digits and
+    // hyphens, but anything that would need escaping is refused regardless.
+    unicode.chars()
+        .all(|c| !c.is_ascii() || c.is_ascii_alphanumeric() || c == '-')
+        .then_some(unicode)
+}
+
+/// Names are displayed as their labels separated by dots, without a trailing
+/// dot, and escaped as described by RFC 1035. The root is displayed as a
+/// single dot.
+impl Display for Name {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_root() {
+            return write!(f, ".");
+        }
+
+        for (index, label) in self.labels.iter().enumerate() {
+            if index > 0 {
+                write!(f, ".")?;
+            }
+
+            write!(f, "{}", EscapedLabel(label))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse a name from its labels separated by dots, undoing the escaping of
+/// RFC 1035. A single trailing dot is allowed, and an empty string or a single
+/// dot results in the root.
+///
+/// Characters outside of ASCII are kept as the UTF-8 octets they are made of,
+/// as used by the instance names of DNS-SD. Internationalized names which are
+/// to be converted to A-labels are parsed with `Name::from_unicode` instead.
+///
+/// https://datatracker.ietf.org/doc/html/rfc6763#section-4.1.3
+impl FromStr for Name {
+    type Err = PresentationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || s == "." {
+            return Ok(Name::root());
+        }
+
+        let mut labels = Vec::new();
+        let mut label = Vec::new();
+        let mut bytes = s.bytes();
+
+        // Whether the last character was an unescaped dot, which is only
+        // allowed at the very end of the name.
+        let mut terminated = false;
+
+        while let Some(byte) = bytes.next() {
+            terminated = false;
+
+            match byte {
+                b'.' => {
+                    if label.is_empty() {
+                        return Err(PresentationError::EmptyLabel(s.to_string()));
+                    }
+
+                    labels.push(std::mem::take(&mut label));
+                    terminated = true;
+                }
+                b'\\' => match bytes.next() {
+                    Some(digit) if digit.is_ascii_digit() => {
+                        let digits = [Some(digit), bytes.next(), bytes.next()];
+                        let mut value: u16 = 0;
+
+                        for digit in digits {
+                            match digit {
+                                Some(digit) if digit.is_ascii_digit() => value = value * 10 + (digit - b'0') as u16,
+                                _ => return Err(PresentationError::InvalidEscape(s.to_string())),
+                            }
+                        }
+
+                        if value > 255 {
+                            return Err(PresentationError::InvalidEscape(s.to_string()));
+                        }
+
+                        label.push(value as u8);
+                    }
+                    Some(escaped) => label.push(escaped),
+                    None => return Err(PresentationError::InvalidEscape(s.to_string())),
+                },
+                _ => label.push(byte),
+            }
+        }
+
+        if !terminated {
+            labels.push(label);
+        }
+
+        if labels.iter().any(|label| label.len() > MAX_LABEL_LENGTH) {
+            return Err(PresentationError::LabelTooLong(s.to_string()));
+        }
+
+        let name = Name { labels };
+
+        if name.wire_length() > MAX_NAME_LENGTH {
+            return Err(PresentationError::NameTooLong(s.to_string()));
+        }
+
+        Ok(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::dns::byte_packet_buffer::BytePacketBuffer;
+    use crate::dns::name::Name;
+
+    fn name(text: &str) -> Name {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn names_are_compared_ignoring_case() {
+        assert_eq!(name("WWW.Example.COM"), name("www.example.com"));
+        assert_ne!(name("www.example.com"), name("example.com"));
+        assert_ne!(name("www.example.com"), name("www.example.org"));
+        assert!(!name("WWW.Example.COM").eq_case_sensitive(&name("www.example.com")));
+
+        let mut addresses = HashMap::new();
+        addresses.insert(name("www.example.com"), "10.0.0.1");
+        assert_eq!(addresses.get(&name("WWW.EXAMPLE.com")), Some(&"10.0.0.1"));
+        assert_eq!(addresses.get(&name("example.com")), None);
+    }
+
+    #[test]
+    fn names_are_sorted_in_canonical_order() {
+        // The example of RFC 4034 6.1, written in escaped form.
+        let expected = [
+            "example",
+            "a.example",
+            "yljkjljk.a.example",
+            "Z.a.example",
+            "zABC.a.EXAMPLE",
+            "z.example",
+            "\\001.z.example",
+            "*.z.example",
+            "\\200.z.example",
+        ];
+
+        let mut names: Vec<Name> = expected.iter().rev().map(|text| name(text)).collect();
+        names.sort();
+
+        assert_eq!(names.iter().map(Name::to_string).collect::<Vec<String>>(), expected);
+    }
+
+    #[test]
+    fn subdomains_are_matched_on_whole_labels() {
+        assert!(name("www.example.com").is_subdomain_of(&name("example.com")));
+        assert!(name("www.example.com").is_subdomain_of(&name("EXAMPLE.com")));
+        assert!(name("example.com").is_subdomain_of(&name("example.com")));
+        assert!(name("example.com").is_subdomain_of(&Name::root()));
+
+        assert!(!name("example.com").is_subdomain_of(&name("ample.com")));
+        assert!(!name("example.com").is_subdomain_of(&name("www.example.com")));
+    }
+
+    #[test]
+    fn names_are_walked_label_by_label() {
+        let name = name("www.example.com");
+
+        let ancestors: Vec<String> = name.ancestors().map(|name| name.to_fqdn()).collect();
+        assert_eq!(ancestors, ["www.example.com.", "example.com.", "com.", "."]);
+
+        let descendants: Vec<Name> = name.ancestors().rev().collect();
+        assert_eq!(descendants[0], Name::root());
+        assert_eq!(descendants[1].child(b"example"), descendants[2]);
+        assert_eq!(descendants[2].child(b"www"), name);
+
+        assert_eq!(name.label_count(), 3);
+        assert_eq!(Name::root().ancestors().count(), 1);
+    }
+
+    #[test]
+    fn utf8_labels_are_kept_as_written() {
+        let printer = name("Living Room Printer._ipp._tcp.local");
+        assert_eq!(printer.labels()[0], b"Living Room Printer");
+
+        let books = name("Bücher._http._tcp.local");
+        assert_eq!(books.labels()[0], "Bücher".as_bytes());
+        assert_eq!(books.to_string(), "B\\195\\188cher._http._tcp.local");
+        assert!(name(&books.to_string()).eq_case_sensitive(&books));
+    }
+
+    #[test]
+    #[cfg(feature = "idna")]
+    fn unicode_names_are_stored_as_a_labels() {
+        let books = Name::from_unicode("Bücher.example").unwrap();
+
+        assert_eq!(books.to_string(), "xn--bcher-kva.example");
+        assert_eq!(books, name("xn--bcher-kva.example"));
+
+        // ASCII names are kept exactly as written.
+        assert!(Name::from_unicode("WWW.Example.com").unwrap().eq_case_sensitive(&name("WWW.Example.com")));
+    }
+
+    #[test]
+    #[cfg(feature = "idna")]
+    fn a_labels_are_shown_as_unicode() {
+        let name: Name = "xn--bcher-kva.example".parse().unwrap();
+        assert_eq!(name.to_unicode(), "bücher.example");
+
+        assert_eq!(Name::root().to_unicode(), ".");
+    }
+
+    #[test]
+    #[cfg(feature = "idna")]
+    fn other_labels_are_shown_escaped() {
+        // Neither an invalid A-label nor a label holding a dot is converted.
+        for text in ["xn--abc-.example", "a\\.b.example"] {
+            let name: Name = text.parse().unwrap();
+            assert_eq!(name.to_unicode(), name.to_string(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn escaped_labels_round_trip_through_the_wire() {
+        let text = r"a\.b.back\\slash.with\032space.\200\255.example";
+
+        let parsed = name(text);
+        assert_eq!(parsed.labels(), [&b"a.b"[..], b"back\\slash", b"with space", b"\xC8\xFF", b"example"]);
+
+        let mut buffer = BytePacketBuffer::new();
+        buffer.write_question_name(&parsed).unwrap();
+
+        let wire = buffer.written();
+        assert_eq!(wire, b"\x03a.b\x0Aback\\slash\x0Awith space\x02\xC8\xFF\x07example\x00");
+
+        let read = BytePacketBuffer::from_bytes(wire).read_question_name().unwrap();
+        assert!(read.eq_case_sensitive(&parsed));
+        assert_eq!(read.to_string(), text);
+
+        // Any character may be escaped, and is shown escaped only when needed.
+        assert_eq!(name(r"with\ space.\e\x\a\m\p\l\e").to_string(), r"with\032space.example");
+    }
+}
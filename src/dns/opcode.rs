@@ -0,0 +1,73 @@
+use std::fmt::{Display, Formatter};
+
+/// A four bit field that specifies kind of query in this message. This value
+/// is set by the originator of a query and copied into the response.
+///
+/// https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-5
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Opcode {
+    /// Any opcode not assigned within the registry, kept as its numeric value.
+    UNKNOWN(u8),
+    /// 0 A standard query.
+    QUERY,
+    /// 1 An inverse query (Obsolete).
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc3425
+    IQUERY,
+    /// 2 A server status request.
+    STATUS,
+    /// 4 A notification of a zone change.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc1996
+    NOTIFY,
+    /// 5 A dynamic update of a zone.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc2136
+    UPDATE,
+    /// 6 DNS Stateful Operations.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc8490
+    DSO,
+}
+
+impl Opcode {
+    pub fn to_num(self) -> u8 {
+        match self {
+            Opcode::UNKNOWN(x) => x,
+            Opcode::QUERY => 0,
+            Opcode::IQUERY => 1,
+            Opcode::STATUS => 2,
+            Opcode::NOTIFY => 4,
+            Opcode::UPDATE => 5,
+            Opcode::DSO => 6,
+        }
+    }
+
+    pub fn from_num(num: u8) -> Opcode {
+        match num {
+            0 => Opcode::QUERY,
+            1 => Opcode::IQUERY,
+            2 => Opcode::STATUS,
+            4 => Opcode::NOTIFY,
+            5 => Opcode::UPDATE,
+            6 => Opcode::DSO,
+            _ => Opcode::UNKNOWN(num),
+        }
+    }
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mnemonic = match self {
+            Opcode::UNKNOWN(x) => return write!(f, "OPCODE{}", x),
+            Opcode::QUERY => "QUERY",
+            Opcode::IQUERY => "IQUERY",
+            Opcode::STATUS => "STATUS",
+            Opcode::NOTIFY => "NOTIFY",
+            Opcode::UPDATE => "UPDATE",
+            Opcode::DSO => "DSO",
+        };
+
+        write!(f, "{}", mnemonic)
+    }
+}
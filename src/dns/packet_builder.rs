@@ -0,0 +1,288 @@
+use crate::dns::build_error::BuildError;
+use crate::dns::dns_header::DnsHeader;
+use crate::dns::dns_packet::DnsPacket;
+use crate::dns::dns_question::DnsQuestion;
+use crate::dns::dns_record::DnsRecord;
+use crate::dns::edns::Edns;
+use crate::dns::parse_error::Section;
+use crate::dns::query_class::QueryClass;
+use crate::dns::result_code::ResultCode;
+
+/// Builds a query for a single question, started with `DnsPacket::query`.
+///
+/// The query gets a random id and the IN class, and no flags are set until
+/// asked for. As with `ResponseBuilder`, every setter takes and returns
+/// `&mut self`, so a query is built either in a single chain or step by step:
+///
+///     let query = DnsPacket::query(name, QueryType::A)
+///         .recursion_desired(true)
+///         .edns(Edns::new(DEFAULT_UDP_PAYLOAD_SIZE))
+///         .build();
+#[derive(Clone, Debug)]
+pub struct QueryBuilder {
+    packet: DnsPacket,
+}
+
+impl QueryBuilder {
+    pub(crate) fn new(question: DnsQuestion) -> QueryBuilder {
+        let mut packet = DnsPacket::new();
+        packet.header.id = rand::random();
+        packet.questions.push(question);
+
+        QueryBuilder { packet }
+    }
+
+    #[allow(dead_code)]
+    pub fn id(&mut self, id: u16) -> &mut QueryBuilder {
+        self.packet.header.id = id;
+        self
+    }
+
+    /// Ask the server to pursue the query recursively, rather than only
+    /// answering from its own data.
+    pub fn recursion_desired(&mut self, recursion_desired: bool) -> &mut QueryBuilder {
+        self.packet.header.recursion_desired = recursion_desired;
+        self
+    }
+
+    /// Ask the server not to perform DNSSEC validation.
+    #[allow(dead_code)]
+    pub fn checking_disabled(&mut self, checking_disabled: bool) -> &mut QueryBuilder {
+        self.packet.header.checking_disabled = checking_disabled;
+        self
+    }
+
+    /// Ask the server whether the answer was validated with DNSSEC.
+    #[allow(dead_code)]
+    pub fn authed_data(&mut self, authed_data: bool) -> &mut QueryBuilder {
+        self.packet.header.authed_data = authed_data;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn class(&mut self, class: QueryClass) -> &mut QueryBuilder {
+        self.packet.questions[0].q_class = class;
+        self
+    }
+
+    pub fn edns(&mut self, edns: Edns) -> &mut QueryBuilder {
+        self.packet.edns = Some(edns);
+        self
+    }
+
+    pub fn build(&self) -> DnsPacket {
+        let mut packet = self.packet.clone();
+
+        packet.header.questions = packet.questions.len() as u16;
+
+        packet
+    }
+}
+
+/// Builds the response to a request, started with `DnsPacket::response_to`
+/// or `DnsPacketView::response`.
+///
+/// The id, opcode, RD flag and questions of the request are copied into the
+/// response. Records are added section by section, and each one is checked to
+/// belong where it is put. A record which doesn't is refused, leaving the
+/// builder as it was, so the caller may skip over it and carry on:
+///
+///     let mut response = DnsPacket::response_to(&request);
+///     response.recursion_available(true);
+///     response.answer(record)?;
+///     let response = response.build();
+#[derive(Clone, Debug)]
+pub struct ResponseBuilder {
+    packet: DnsPacket,
+}
+
+impl ResponseBuilder {
+    pub(crate) fn new(request: &DnsHeader, questions: Vec<DnsQuestion>) -> ResponseBuilder {
+        let mut packet = DnsPacket::new();
+        packet.header.id = request.id;
+        packet.header.opcode = request.opcode;
+        packet.header.recursion_desired = request.recursion_desired;
+        packet.header.response = true;
+        packet.questions = questions;
+
+        ResponseBuilder { packet }
+    }
+
+    /// The question being answered, which is the first question of the
+    /// request.
+    pub fn question(&self) -> Option<&DnsQuestion> {
+        self.packet.questions.first()
+    }
+
+    pub fn recursion_available(&mut self, recursion_available: bool) -> &mut ResponseBuilder {
+        self.packet.header.recursion_available = recursion_available;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn authoritative(&mut self, authoritative: bool) -> &mut ResponseBuilder {
+        self.packet.header.authoritative_answer = authoritative;
+        self
+    }
+
+    /// Set the response code. A code which does not fit within the header
+    /// needs the EDNS information to be set first, to carry its upper bits.
+    pub fn rcode(&mut self, rcode: ResultCode) -> Result<&mut ResponseBuilder, BuildError> {
+        if rcode.extended_bits() != 0 && self.packet.edns.is_none() {
+            return Err(BuildError::ExtendedRcodeWithoutEdns(rcode));
+        }
+
+        self.packet.header.rescode = rcode;
+        Ok(self)
+    }
+
+    pub fn edns(&mut self, edns: Edns) -> &mut ResponseBuilder {
+        self.packet.edns = Some(edns);
+        self
+    }
+
+    /// Add a record to the answer section, which requires a question for it
+    /// to answer.
+    pub fn answer(&mut self, record: DnsRecord) -> Result<&mut ResponseBuilder, BuildError> {
+        check_not_opt(&record, Section::Answer)?;
+
+        if self.packet.questions.is_empty() {
+            return Err(BuildError::AnswerWithoutQuestion);
+        }
+
+        self.packet.answers.push(record);
+        Ok(self)
+    }
+
+    /// Add a record to the authority section, which only holds the NS records
+    /// of a referral or the SOA record of a negative answer.
+    pub fn authority(&mut self, record: DnsRecord) -> Result<&mut ResponseBuilder, BuildError> {
+        check_not_opt(&record, Section::Authority)?;
+
+        // Records of types which are not understood are let through, as
+        // DNSSEC adds its own records such as NSEC and RRSIG to the section.
+        match record {
+            DnsRecord::NS { .. } | DnsRecord::SOA { .. } | DnsRecord::UNHANDLED { .. } => {}
+            _ => return Err(BuildError::InvalidAuthorityRecord(record.qtype())),
+        }
+
+        self.packet.authorities.push(record);
+        Ok(self)
+    }
+
+    pub fn additional(&mut self, record: DnsRecord) -> Result<&mut ResponseBuilder, BuildError> {
+        check_not_opt(&record, Section::Additional)?;
+
+        self.packet.resources.push(record);
+        Ok(self)
+    }
+
+    /// Finish the response, with the counts of the header matching its
+    /// sections.
+    pub fn build(&self) -> DnsPacket {
+        let mut packet = self.packet.clone();
+
+        packet.header.questions = packet.questions.len() as u16;
+        packet.header.answers = packet.answers.len() as u16;
+        packet.header.authoritative_entries = packet.authorities.len() as u16;
+        packet.header.resource_entries = (packet.resources.len() + packet.edns.is_some() as usize) as u16;
+
+        packet
+    }
+}
+
+// The EDNS information of a message is set on its own instead, and always
+// written as the last record of the additional section.
+fn check_not_opt(record: &DnsRecord, section: Section) -> Result<(), BuildError> {
+    match record {
+        DnsRecord::OPT { .. } => Err(BuildError::OptRecord(section)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use crate::dns::build_error::BuildError;
+    use crate::dns::dns_header::DnsHeader;
+    use crate::dns::dns_packet::DnsPacket;
+    use crate::dns::dns_question::DnsQuestion;
+    use crate::dns::dns_record::DnsRecord;
+    use crate::dns::edns::Edns;
+    use crate::dns::packet_builder::ResponseBuilder;
+    use crate::dns::parse_error::Section;
+    use crate::dns::query_class::QueryClass;
+    use crate::dns::query_type::QueryType;
+    use crate::dns::result_code::ResultCode;
+
+    fn response(questions: usize) -> ResponseBuilder {
+        let question = DnsQuestion::new("example.com".parse().unwrap(), QueryType::A, QueryClass::IN);
+        ResponseBuilder::new(&DnsHeader::new(), vec![question; questions])
+    }
+
+    fn a() -> DnsRecord {
+        DnsRecord::A { domain: "example.com".parse().unwrap(), addr: Ipv4Addr::new(10, 0, 0, 1), ttl: 300 }
+    }
+
+    fn ns() -> DnsRecord {
+        DnsRecord::NS { domain: "example.com".parse().unwrap(), host: "ns1.example.com".parse().unwrap(), ttl: 300 }
+    }
+
+    #[test]
+    fn records_are_added_to_their_sections() {
+        let mut builder = response(1);
+        builder.answer(a()).unwrap().authority(ns()).unwrap().additional(a()).unwrap();
+        builder.edns(Edns::new(1232));
+
+        let packet = builder.build();
+
+        assert!(packet.header.response);
+        assert_eq!((packet.answers.len(), packet.authorities.len(), packet.resources.len()), (1, 1, 1));
+        assert_eq!((packet.header.questions, packet.header.answers, packet.header.authoritative_entries, packet.header.resource_entries), (1, 1, 1, 2));
+    }
+
+    #[test]
+    fn refused_records_leave_the_builder_untouched() {
+        let mut builder = response(1);
+
+        assert_eq!(builder.authority(a()).unwrap_err(), BuildError::InvalidAuthorityRecord(QueryType::A));
+        assert_eq!(builder.additional(DnsRecord::OPT { edns: Edns::new(512) }).unwrap_err(), BuildError::OptRecord(Section::Additional));
+
+        builder.authority(ns()).unwrap();
+
+        let packet = builder.build();
+        assert_eq!(packet.authorities, vec![ns()]);
+        assert!(packet.resources.is_empty());
+    }
+
+    #[test]
+    fn answers_need_a_question() {
+        assert_eq!(response(0).answer(a()).unwrap_err(), BuildError::AnswerWithoutQuestion);
+    }
+
+    #[test]
+    fn extended_rcodes_need_edns() {
+        let mut builder = response(1);
+        assert_eq!(builder.rcode(ResultCode::BADVERS).unwrap_err(), BuildError::ExtendedRcodeWithoutEdns(ResultCode::BADVERS));
+
+        builder.edns(Edns::new(1232));
+        builder.rcode(ResultCode::BADVERS).unwrap();
+        assert_eq!(builder.build().header.rescode, ResultCode::BADVERS);
+    }
+
+    #[test]
+    fn queries_are_built_step_by_step() {
+        let mut builder = DnsPacket::query("example.com".parse().unwrap(), QueryType::TXT);
+        builder.id(0x1234).recursion_desired(true);
+        builder.class(QueryClass::CH);
+
+        let packet = builder.build();
+
+        assert_eq!(packet.header.id, 0x1234);
+        assert!(packet.header.recursion_desired);
+        assert!(!packet.header.response);
+        assert_eq!(packet.header.questions, 1);
+        assert_eq!(packet.questions[0].q_class, QueryClass::CH);
+        assert_eq!(packet.edns, None);
+    }
+}
@@ -0,0 +1,60 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use crate::dns::byte_packet_buffer_error::BytePacketBufferError;
+
+/// The part of a message in which parsing failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Header,
+    Question,
+    Answer,
+    Authority,
+    Additional,
+    /// After the last record of the additional section, where a message must
+    /// end.
+    End,
+}
+
+impl Display for Section {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Section::Header => write!(f, "header"),
+            Section::Question => write!(f, "question section"),
+            Section::Answer => write!(f, "answer section"),
+            Section::Authority => write!(f, "authority section"),
+            Section::Additional => write!(f, "additional section"),
+            Section::End => write!(f, "end of message"),
+        }
+    }
+}
+
+/// A failure to parse a message, along with where in the message it happened.
+#[derive(Debug)]
+pub struct ParseError {
+    /// The section being read when parsing failed.
+    pub section: Section,
+    /// The index of the question or record within the section, counting from
+    /// zero. Always zero for the header and the end of the message.
+    pub index: usize,
+    /// The byte offset within the message at which the header, question or
+    /// record being read starts, or at which the trailing data of a message
+    /// starts.
+    pub offset: usize,
+    /// The underlying reason parsing failed.
+    pub error: BytePacketBufferError,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.section {
+            Section::Header | Section::End => write!(f, "{} in {} at offset {}", self.error, self.section, self.offset),
+            _ => write!(f, "{} in {} entry {} at offset {}", self.error, self.section, self.index, self.offset),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
@@ -0,0 +1,155 @@
+use std::fmt::{Display, Formatter};
+use crate::dns::presentation_error::PresentationError;
+
+/// Split a single line of master file text into its tokens.
+///
+/// RFC 1035
+/// 5.1. Format
+///
+/// Tokens are separated by blanks. A quoted string may contain blanks, and a
+/// backslash quotes the character following it. Parentheses group data which
+/// crosses a line boundary and are treated as blanks, as only a single line is
+/// ever read. A semicolon starts a comment which runs to the end of the line.
+///
+/// Tokens are returned exactly as written, including any quotes and escapes,
+/// so that each can be interpreted according to its position in the line.
+pub fn tokenize(line: &str) -> Result<Vec<&str>, PresentationError> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for (index, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+
+        match c {
+            '\\' => {
+                escaped = true;
+                start.get_or_insert(index);
+            }
+            '"' if quoted => {
+                quoted = false;
+                tokens.push(&line[start.take().unwrap_or(index)..=index]);
+            }
+            '"' if start.is_none() => {
+                quoted = true;
+                start = Some(index);
+            }
+            _ if quoted => {}
+            ';' => break,
+            ' ' | '\t' | '(' | ')' => {
+                if let Some(start) = start.take() {
+                    tokens.push(&line[start..index]);
+                }
+            }
+            _ => {
+                start.get_or_insert(index);
+            }
+        }
+    }
+
+    if quoted || escaped {
+        return Err(PresentationError::InvalidRecord(line.to_string()));
+    }
+
+    if let Some(start) = start {
+        tokens.push(&line[start..]);
+    }
+
+    Ok(tokens)
+}
+
+/// Parse a <character-string>, which is either a quoted string or a single
+/// token, undoing the escaping of RFC 1035.
+pub fn parse_character_string(token: &str) -> Result<Vec<u8>, PresentationError> {
+    let value = match token.strip_prefix('"') {
+        Some(value) => value.strip_suffix('"').ok_or_else(|| PresentationError::InvalidRecord(token.to_string()))?,
+        None => token,
+    };
+
+    let mut result = Vec::with_capacity(value.len());
+    let mut bytes = value.bytes();
+
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            result.push(byte);
+            continue;
+        }
+
+        match bytes.next() {
+            Some(digit) if digit.is_ascii_digit() => {
+                let mut value: u16 = 0;
+
+                for digit in [Some(digit), bytes.next(), bytes.next()] {
+                    match digit {
+                        Some(digit) if digit.is_ascii_digit() => value = value * 10 + (digit - b'0') as u16,
+                        _ => return Err(PresentationError::InvalidEscape(token.to_string())),
+                    }
+                }
+
+                result.push(u8::try_from(value).map_err(|_| PresentationError::InvalidEscape(token.to_string()))?);
+            }
+            Some(escaped) => result.push(escaped),
+            None => return Err(PresentationError::InvalidEscape(token.to_string())),
+        }
+    }
+
+    Ok(result)
+}
+
+/// A <character-string> displayed as a quoted string. Quotes and backslashes
+/// are escaped, as is anything outside of printable ASCII.
+pub struct CharacterString<'a>(pub &'a [u8]);
+
+impl Display for CharacterString<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"")?;
+
+        for byte in self.0 {
+            match byte {
+                b'"' | b'\\' => write!(f, "\\{}", *byte as char)?,
+                0x20..=0x7E => write!(f, "{}", *byte as char)?,
+                _ => write!(f, "\\{:03}", byte)?,
+            }
+        }
+
+        write!(f, "\"")
+    }
+}
+
+/// Parse record data written in the generic form of RFC 3597: the `\#` token,
+/// the length of the data and the data itself in hexadecimal, which may be
+/// split over any number of tokens. Returns `None` when the data is not in
+/// the generic form.
+///
+/// https://datatracker.ietf.org/doc/html/rfc3597#section-5
+pub fn parse_generic_rdata(tokens: &[&str]) -> Option<Result<Vec<u8>, PresentationError>> {
+    let (marker, rest) = tokens.split_first()?;
+    if *marker != "\\#" {
+        return None;
+    }
+
+    let invalid = || PresentationError::InvalidRecord(tokens.join(" "));
+
+    let Some((length, hex)) = rest.split_first() else {
+        return Some(Err(invalid()));
+    };
+
+    let Ok(length) = length.parse::<usize>() else {
+        return Some(Err(invalid()));
+    };
+
+    let hex = hex.concat();
+    if Some(hex.len()) != length.checked_mul(2) || !hex.bytes().all(|x| x.is_ascii_hexdigit()) {
+        return Some(Err(invalid()));
+    }
+
+    let data = (0..hex.len()).step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).map_err(|_| invalid()))
+        .collect();
+
+    Some(data)
+}
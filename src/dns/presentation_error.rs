@@ -0,0 +1,47 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PresentationError {
+    /// The text is neither the mnemonic of a known record type nor the generic
+    /// `TYPE123` form.
+    UnknownType(String),
+    /// The text is neither the mnemonic of a known class nor the generic
+    /// `CLASS45` form.
+    UnknownClass(String),
+    /// The name contains an empty label, such as `example..com`.
+    EmptyLabel(String),
+    /// A label within the name exceeds 63 octets.
+    LabelTooLong(String),
+    /// The name exceeds 255 octets in its wire form.
+    NameTooLong(String),
+    /// A backslash within a name or string is followed by neither a character nor
+    /// three decimal digits of at most 255, such as `\256` or a trailing `\`.
+    InvalidEscape(String),
+    /// The name contains characters outside of ASCII but cannot be converted
+    /// to A-labels following UTS #46, such as a label with disallowed code
+    /// points or invalid bidirectional text.
+    #[cfg(feature = "idna")]
+    InvalidIdna(String),
+    /// The text is not a valid record in the master file format, such as a
+    /// line with missing fields or data which does not suit the record type.
+    InvalidRecord(String),
+}
+
+impl Display for PresentationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PresentationError::UnknownType(value) => write!(f, "unknown record type: {:?}", value),
+            PresentationError::UnknownClass(value) => write!(f, "unknown class: {:?}", value),
+            PresentationError::EmptyLabel(value) => write!(f, "empty label in name: {:?}", value),
+            PresentationError::LabelTooLong(value) => write!(f, "label exceeded 63 octets in name: {:?}", value),
+            PresentationError::NameTooLong(value) => write!(f, "name exceeded 255 octets: {:?}", value),
+            PresentationError::InvalidEscape(value) => write!(f, "invalid escape: {:?}", value),
+            #[cfg(feature = "idna")]
+            PresentationError::InvalidIdna(value) => write!(f, "invalid internationalized name: {:?}", value),
+            PresentationError::InvalidRecord(value) => write!(f, "invalid record: {:?}", value),
+        }
+    }
+}
+
+impl Error for PresentationError {}
@@ -1,7 +1,13 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::dns::presentation_error::PresentationError;
+
 /// https://datatracker.ietf.org/doc/html/rfc1035#section-3.2.4
 ///
 /// CLASS fields appear in resource records.
-#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+///
+/// https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-2
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Copy)]
 pub enum QueryClass {
     UNKNOWN(u16),
     /// 1 The Internet
@@ -13,16 +19,25 @@ pub enum QueryClass {
     CH,
     /// 4 Hesiod [Dyer 87]
     HS,
+    /// 254 Used within dynamic update messages to delete records, matching no
+    /// class at all.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc2136#section-2.4
+    NONE,
+    /// 255 A QCLASS matching any class
+    ANY,
 }
 
 impl QueryClass {
-    pub fn to_num(&self) -> u16 {
-        match *self {
+    pub fn to_num(self) -> u16 {
+        match self {
             QueryClass::UNKNOWN(x) => x,
             QueryClass::IN => 1,
             QueryClass::CS => 2,
             QueryClass::CH => 3,
             QueryClass::HS => 4,
+            QueryClass::NONE => 254,
+            QueryClass::ANY => 255,
         }
     }
 
@@ -32,7 +47,59 @@ impl QueryClass {
             2 => QueryClass::CS,
             3 => QueryClass::CH,
             4 => QueryClass::HS,
+            254 => QueryClass::NONE,
+            255 => QueryClass::ANY,
             _ => QueryClass::UNKNOWN(num),
         }
     }
-}
\ No newline at end of file
+}
+
+/// RFC 3597
+/// 5. Text Representation
+///
+/// Classes are presented by their mnemonic, while classes without a mnemonic
+/// are presented as the word "CLASS" immediately followed by the class number,
+/// for example `CLASS45`.
+impl Display for QueryClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mnemonic = match self {
+            QueryClass::UNKNOWN(x) => return write!(f, "CLASS{}", x),
+            QueryClass::IN => "IN",
+            QueryClass::CS => "CS",
+            QueryClass::CH => "CH",
+            QueryClass::HS => "HS",
+            QueryClass::NONE => "NONE",
+            QueryClass::ANY => "ANY",
+        };
+
+        write!(f, "{}", mnemonic)
+    }
+}
+
+/// Parse a class from either its mnemonic or the generic `CLASS45` form, both
+/// case-insensitive.
+impl FromStr for QueryClass {
+    type Err = PresentationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s.to_uppercase();
+
+        if let Some(num) = value.strip_prefix("CLASS") {
+            if let Ok(num) = num.parse::<u16>() {
+                return Ok(QueryClass::from_num(num));
+            }
+        }
+
+        let result = match value.as_str() {
+            "IN" => QueryClass::IN,
+            "CS" => QueryClass::CS,
+            "CH" => QueryClass::CH,
+            "HS" => QueryClass::HS,
+            "NONE" => QueryClass::NONE,
+            "ANY" | "*" => QueryClass::ANY,
+            _ => return Err(PresentationError::UnknownClass(s.to_string())),
+        };
+
+        Ok(result)
+    }
+}
@@ -1,6 +1,15 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::dns::presentation_error::PresentationError;
+
 /// A two octet code which specifies the type of the query. The values for this
 /// field include all codes valid for a TYPE field, together with some more
 /// general codes which can match more than one type of RR.
+///
+/// The types follow the IANA registry of resource record types, any type not
+/// listed here is kept as `UNKNOWN` with its numeric value.
+///
+/// https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-4
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Copy)]
 pub enum QueryType {
     UNKNOWN(u16),
@@ -64,11 +73,147 @@ pub enum QueryType {
     ///
     /// https://datatracker.ietf.org/doc/html/rfc1035#section-3.3.14
     TXT,
+    /// 17 for Responsible Person
+    RP,
+    /// 18 for AFS Data Base location
+    AFSDB,
+    /// 19 for X.25 PSDN address
+    X25,
+    /// 20 for ISDN address
+    ISDN,
+    /// 21 for Route Through
+    RT,
+    /// 22 for NSAP address, NSAP style A record (DEPRECATED)
+    NSAP,
+    /// 23 for domain name pointer, NSAP style (DEPRECATED)
+    NSAPPTR,
+    /// 24 for security signature
+    SIG,
+    /// 25 for security key
+    KEY,
+    /// 26 X.400 mail mapping information
+    PX,
+    /// 27 Geographical Position
+    GPOS,
     /// AAAA A record specific to the Internet class that stores a single IPv6
     /// address. The IANA assigned value of the type is 28 (decimal).
     ///
     /// https://datatracker.ietf.org/doc/html/rfc3596#section-2.1
     AAAA,
+    /// 29 Location Information
+    LOC,
+    /// 30 Next Domain (OBSOLETE)
+    NXT,
+    /// 31 Endpoint Identifier
+    EID,
+    /// 32 Nimrod Locator
+    NIMLOC,
+    /// 33 The location of a service, specifying the host and port of the
+    /// servers for a protocol within a domain.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc2782
+    SRV,
+    /// 34 ATM Address
+    ATMA,
+    /// 35 Naming Authority Pointer
+    NAPTR,
+    /// 36 Key Exchanger
+    KX,
+    /// 37 A certificate or certificate revocation list
+    CERT,
+    /// 38 An IPv6 address (OBSOLETE - use AAAA)
+    A6,
+    /// 39 Delegation of an entire subtree of the domain name space
+    DNAME,
+    /// 40 Kitchen sink
+    SINK,
+    /// 41 The OPT pseudo-record, carrying EDNS(0) information in the additional
+    /// section of a message. It never holds actual data about a domain.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc6891#section-6.1
+    OPT,
+    /// 42 Address prefix list
+    APL,
+    /// 43 Delegation Signer
+    DS,
+    /// 44 SSH Key Fingerprint
+    SSHFP,
+    /// 45 IPsec keying material
+    IPSECKEY,
+    /// 46 A DNSSEC signature over a RRset
+    RRSIG,
+    /// 47 The next secure name, proving the non-existence of names
+    NSEC,
+    /// 48 A DNSSEC public key
+    DNSKEY,
+    /// 49 DHCP identifier
+    DHCID,
+    /// 50 The hashed next secure name
+    NSEC3,
+    /// 51 The parameters used by NSEC3 hashing
+    NSEC3PARAM,
+    /// 52 A TLS certificate association
+    TLSA,
+    /// 53 An S/MIME certificate association
+    SMIMEA,
+    /// 55 Host Identity Protocol
+    HIP,
+    /// 56 Zone status information
+    NINFO,
+    /// 57 Resource key
+    RKEY,
+    /// 58 Trust Anchor LINK
+    TALINK,
+    /// 59 Child DS
+    CDS,
+    /// 60 DNSKEY(s) the Child wants reflected in DS
+    CDNSKEY,
+    /// 61 OpenPGP Key
+    OPENPGPKEY,
+    /// 62 Child-To-Parent Synchronization
+    CSYNC,
+    /// 63 Message Digest Over Zone Data
+    ZONEMD,
+    /// 64 General-purpose service binding
+    SVCB,
+    /// 65 SVCB-compatible type for use with HTTP
+    HTTPS,
+    /// 66 Endpoint discovery for delegation synchronization
+    DSYNC,
+    /// 67 Hierarchical Host Identity Tag
+    HHIT,
+    /// 68 UAS Broadcast Remote Identification
+    BRID,
+    /// 99 Sender Policy Framework (OBSOLETE - use TXT)
+    SPF,
+    /// 100 Reserved by IANA
+    UINFO,
+    /// 101 Reserved by IANA
+    UID,
+    /// 102 Reserved by IANA
+    GID,
+    /// 103 Reserved by IANA
+    UNSPEC,
+    /// 104 An ILNP node identifier
+    NID,
+    /// 105 An ILNP 32 bit locator
+    L32,
+    /// 106 An ILNP 64 bit locator
+    L64,
+    /// 107 An ILNP locator pointer
+    LP,
+    /// 108 An EUI-48 address
+    EUI48,
+    /// 109 An EUI-64 address
+    EUI64,
+    /// 128 NXDOMAIN indicator for Compact Denial of Existence
+    NXNAME,
+    /// 249 Transaction Key
+    TKEY,
+    /// 250 Transaction Signature
+    TSIG,
+    /// 251 A request for an incremental transfer of a zone
+    IXFR,
     /// 252 A request for a transfer of an entire zone
     AXFR,
     /// 253 A request for mailbox-related records (MB, MG or MR)
@@ -77,11 +222,33 @@ pub enum QueryType {
     MAILA,
     /// 255 A request for all records
     EVERYTHING,
+    /// 256 A URI
+    URI,
+    /// 257 Certification Authority Restriction
+    CAA,
+    /// 258 Application Visibility and Control
+    AVC,
+    /// 259 Digital Object Architecture
+    DOA,
+    /// 260 Automatic Multicast Tunneling Relay
+    AMTRELAY,
+    /// 261 Resolver Information as Key/Value Pairs
+    RESINFO,
+    /// 262 Public wallet address
+    WALLET,
+    /// 263 BP Convergence Layer Adapter
+    CLA,
+    /// 264 BP Node Number
+    IPN,
+    /// 32768 DNSSEC Trust Authorities
+    TA,
+    /// 32769 DNSSEC Lookaside Validation (OBSOLETE)
+    DLV,
 }
 
 impl QueryType {
-    pub fn to_num(&self) -> u16 {
-        match *self {
+    pub fn to_num(self) -> u16 {
+        match self {
             QueryType::UNKNOWN(x) => x,
             QueryType::A => 1,
             QueryType::NS => 2,
@@ -99,11 +266,87 @@ impl QueryType {
             QueryType::MINFO => 14,
             QueryType::MX => 15,
             QueryType::TXT => 16,
+            QueryType::RP => 17,
+            QueryType::AFSDB => 18,
+            QueryType::X25 => 19,
+            QueryType::ISDN => 20,
+            QueryType::RT => 21,
+            QueryType::NSAP => 22,
+            QueryType::NSAPPTR => 23,
+            QueryType::SIG => 24,
+            QueryType::KEY => 25,
+            QueryType::PX => 26,
+            QueryType::GPOS => 27,
             QueryType::AAAA => 28,
+            QueryType::LOC => 29,
+            QueryType::NXT => 30,
+            QueryType::EID => 31,
+            QueryType::NIMLOC => 32,
+            QueryType::SRV => 33,
+            QueryType::ATMA => 34,
+            QueryType::NAPTR => 35,
+            QueryType::KX => 36,
+            QueryType::CERT => 37,
+            QueryType::A6 => 38,
+            QueryType::DNAME => 39,
+            QueryType::SINK => 40,
+            QueryType::OPT => 41,
+            QueryType::APL => 42,
+            QueryType::DS => 43,
+            QueryType::SSHFP => 44,
+            QueryType::IPSECKEY => 45,
+            QueryType::RRSIG => 46,
+            QueryType::NSEC => 47,
+            QueryType::DNSKEY => 48,
+            QueryType::DHCID => 49,
+            QueryType::NSEC3 => 50,
+            QueryType::NSEC3PARAM => 51,
+            QueryType::TLSA => 52,
+            QueryType::SMIMEA => 53,
+            QueryType::HIP => 55,
+            QueryType::NINFO => 56,
+            QueryType::RKEY => 57,
+            QueryType::TALINK => 58,
+            QueryType::CDS => 59,
+            QueryType::CDNSKEY => 60,
+            QueryType::OPENPGPKEY => 61,
+            QueryType::CSYNC => 62,
+            QueryType::ZONEMD => 63,
+            QueryType::SVCB => 64,
+            QueryType::HTTPS => 65,
+            QueryType::DSYNC => 66,
+            QueryType::HHIT => 67,
+            QueryType::BRID => 68,
+            QueryType::SPF => 99,
+            QueryType::UINFO => 100,
+            QueryType::UID => 101,
+            QueryType::GID => 102,
+            QueryType::UNSPEC => 103,
+            QueryType::NID => 104,
+            QueryType::L32 => 105,
+            QueryType::L64 => 106,
+            QueryType::LP => 107,
+            QueryType::EUI48 => 108,
+            QueryType::EUI64 => 109,
+            QueryType::NXNAME => 128,
+            QueryType::TKEY => 249,
+            QueryType::TSIG => 250,
+            QueryType::IXFR => 251,
             QueryType::AXFR => 252,
             QueryType::MAILB => 253,
             QueryType::MAILA => 254,
             QueryType::EVERYTHING => 255,
+            QueryType::URI => 256,
+            QueryType::CAA => 257,
+            QueryType::AVC => 258,
+            QueryType::DOA => 259,
+            QueryType::AMTRELAY => 260,
+            QueryType::RESINFO => 261,
+            QueryType::WALLET => 262,
+            QueryType::CLA => 263,
+            QueryType::IPN => 264,
+            QueryType::TA => 32768,
+            QueryType::DLV => 32769,
         }
     }
 
@@ -125,11 +368,339 @@ impl QueryType {
             14 => QueryType::MINFO,
             15 => QueryType::MX,
             16 => QueryType::TXT,
+            17 => QueryType::RP,
+            18 => QueryType::AFSDB,
+            19 => QueryType::X25,
+            20 => QueryType::ISDN,
+            21 => QueryType::RT,
+            22 => QueryType::NSAP,
+            23 => QueryType::NSAPPTR,
+            24 => QueryType::SIG,
+            25 => QueryType::KEY,
+            26 => QueryType::PX,
+            27 => QueryType::GPOS,
+            28 => QueryType::AAAA,
+            29 => QueryType::LOC,
+            30 => QueryType::NXT,
+            31 => QueryType::EID,
+            32 => QueryType::NIMLOC,
+            33 => QueryType::SRV,
+            34 => QueryType::ATMA,
+            35 => QueryType::NAPTR,
+            36 => QueryType::KX,
+            37 => QueryType::CERT,
+            38 => QueryType::A6,
+            39 => QueryType::DNAME,
+            40 => QueryType::SINK,
+            41 => QueryType::OPT,
+            42 => QueryType::APL,
+            43 => QueryType::DS,
+            44 => QueryType::SSHFP,
+            45 => QueryType::IPSECKEY,
+            46 => QueryType::RRSIG,
+            47 => QueryType::NSEC,
+            48 => QueryType::DNSKEY,
+            49 => QueryType::DHCID,
+            50 => QueryType::NSEC3,
+            51 => QueryType::NSEC3PARAM,
+            52 => QueryType::TLSA,
+            53 => QueryType::SMIMEA,
+            55 => QueryType::HIP,
+            56 => QueryType::NINFO,
+            57 => QueryType::RKEY,
+            58 => QueryType::TALINK,
+            59 => QueryType::CDS,
+            60 => QueryType::CDNSKEY,
+            61 => QueryType::OPENPGPKEY,
+            62 => QueryType::CSYNC,
+            63 => QueryType::ZONEMD,
+            64 => QueryType::SVCB,
+            65 => QueryType::HTTPS,
+            66 => QueryType::DSYNC,
+            67 => QueryType::HHIT,
+            68 => QueryType::BRID,
+            99 => QueryType::SPF,
+            100 => QueryType::UINFO,
+            101 => QueryType::UID,
+            102 => QueryType::GID,
+            103 => QueryType::UNSPEC,
+            104 => QueryType::NID,
+            105 => QueryType::L32,
+            106 => QueryType::L64,
+            107 => QueryType::LP,
+            108 => QueryType::EUI48,
+            109 => QueryType::EUI64,
+            128 => QueryType::NXNAME,
+            249 => QueryType::TKEY,
+            250 => QueryType::TSIG,
+            251 => QueryType::IXFR,
             252 => QueryType::AXFR,
             253 => QueryType::MAILB,
             254 => QueryType::MAILA,
             255 => QueryType::EVERYTHING,
+            256 => QueryType::URI,
+            257 => QueryType::CAA,
+            258 => QueryType::AVC,
+            259 => QueryType::DOA,
+            260 => QueryType::AMTRELAY,
+            261 => QueryType::RESINFO,
+            262 => QueryType::WALLET,
+            263 => QueryType::CLA,
+            264 => QueryType::IPN,
+            32768 => QueryType::TA,
+            32769 => QueryType::DLV,
             _ => QueryType::UNKNOWN(num),
         }
     }
-}
\ No newline at end of file
+}
+
+/// RFC 3597
+/// 5. Text Representation
+///
+/// Types are presented by their mnemonic, while types without a mnemonic are
+/// presented as the word "TYPE" immediately followed by the type number, for
+/// example `TYPE731`.
+impl Display for QueryType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mnemonic = match self {
+            QueryType::UNKNOWN(x) => return write!(f, "TYPE{}", x),
+            QueryType::A => "A",
+            QueryType::NS => "NS",
+            QueryType::MD => "MD",
+            QueryType::MF => "MF",
+            QueryType::CNAME => "CNAME",
+            QueryType::SOA => "SOA",
+            QueryType::MB => "MB",
+            QueryType::MG => "MG",
+            QueryType::MR => "MR",
+            QueryType::NULL => "NULL",
+            QueryType::WKS => "WKS",
+            QueryType::PTR => "PTR",
+            QueryType::HINFO => "HINFO",
+            QueryType::MINFO => "MINFO",
+            QueryType::MX => "MX",
+            QueryType::TXT => "TXT",
+            QueryType::RP => "RP",
+            QueryType::AFSDB => "AFSDB",
+            QueryType::X25 => "X25",
+            QueryType::ISDN => "ISDN",
+            QueryType::RT => "RT",
+            QueryType::NSAP => "NSAP",
+            QueryType::NSAPPTR => "NSAP-PTR",
+            QueryType::SIG => "SIG",
+            QueryType::KEY => "KEY",
+            QueryType::PX => "PX",
+            QueryType::GPOS => "GPOS",
+            QueryType::AAAA => "AAAA",
+            QueryType::LOC => "LOC",
+            QueryType::NXT => "NXT",
+            QueryType::EID => "EID",
+            QueryType::NIMLOC => "NIMLOC",
+            QueryType::SRV => "SRV",
+            QueryType::ATMA => "ATMA",
+            QueryType::NAPTR => "NAPTR",
+            QueryType::KX => "KX",
+            QueryType::CERT => "CERT",
+            QueryType::A6 => "A6",
+            QueryType::DNAME => "DNAME",
+            QueryType::SINK => "SINK",
+            QueryType::OPT => "OPT",
+            QueryType::APL => "APL",
+            QueryType::DS => "DS",
+            QueryType::SSHFP => "SSHFP",
+            QueryType::IPSECKEY => "IPSECKEY",
+            QueryType::RRSIG => "RRSIG",
+            QueryType::NSEC => "NSEC",
+            QueryType::DNSKEY => "DNSKEY",
+            QueryType::DHCID => "DHCID",
+            QueryType::NSEC3 => "NSEC3",
+            QueryType::NSEC3PARAM => "NSEC3PARAM",
+            QueryType::TLSA => "TLSA",
+            QueryType::SMIMEA => "SMIMEA",
+            QueryType::HIP => "HIP",
+            QueryType::NINFO => "NINFO",
+            QueryType::RKEY => "RKEY",
+            QueryType::TALINK => "TALINK",
+            QueryType::CDS => "CDS",
+            QueryType::CDNSKEY => "CDNSKEY",
+            QueryType::OPENPGPKEY => "OPENPGPKEY",
+            QueryType::CSYNC => "CSYNC",
+            QueryType::ZONEMD => "ZONEMD",
+            QueryType::SVCB => "SVCB",
+            QueryType::HTTPS => "HTTPS",
+            QueryType::DSYNC => "DSYNC",
+            QueryType::HHIT => "HHIT",
+            QueryType::BRID => "BRID",
+            QueryType::SPF => "SPF",
+            QueryType::UINFO => "UINFO",
+            QueryType::UID => "UID",
+            QueryType::GID => "GID",
+            QueryType::UNSPEC => "UNSPEC",
+            QueryType::NID => "NID",
+            QueryType::L32 => "L32",
+            QueryType::L64 => "L64",
+            QueryType::LP => "LP",
+            QueryType::EUI48 => "EUI48",
+            QueryType::EUI64 => "EUI64",
+            QueryType::NXNAME => "NXNAME",
+            QueryType::TKEY => "TKEY",
+            QueryType::TSIG => "TSIG",
+            QueryType::IXFR => "IXFR",
+            QueryType::AXFR => "AXFR",
+            QueryType::MAILB => "MAILB",
+            QueryType::MAILA => "MAILA",
+            QueryType::EVERYTHING => "ANY",
+            QueryType::URI => "URI",
+            QueryType::CAA => "CAA",
+            QueryType::AVC => "AVC",
+            QueryType::DOA => "DOA",
+            QueryType::AMTRELAY => "AMTRELAY",
+            QueryType::RESINFO => "RESINFO",
+            QueryType::WALLET => "WALLET",
+            QueryType::CLA => "CLA",
+            QueryType::IPN => "IPN",
+            QueryType::TA => "TA",
+            QueryType::DLV => "DLV",
+        };
+
+        write!(f, "{}", mnemonic)
+    }
+}
+
+/// Parse a type from either its mnemonic or the generic `TYPE123` form, both
+/// case-insensitive. The generic form of a known type results in the known
+/// type, so `TYPE1` is the same as `A`.
+impl FromStr for QueryType {
+    type Err = PresentationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s.to_uppercase();
+
+        if let Some(num) = value.strip_prefix("TYPE") {
+            if let Ok(num) = num.parse::<u16>() {
+                return Ok(QueryType::from_num(num));
+            }
+        }
+
+        let result = match value.as_str() {
+            "*" => QueryType::EVERYTHING,
+            "A" => QueryType::A,
+            "NS" => QueryType::NS,
+            "MD" => QueryType::MD,
+            "MF" => QueryType::MF,
+            "CNAME" => QueryType::CNAME,
+            "SOA" => QueryType::SOA,
+            "MB" => QueryType::MB,
+            "MG" => QueryType::MG,
+            "MR" => QueryType::MR,
+            "NULL" => QueryType::NULL,
+            "WKS" => QueryType::WKS,
+            "PTR" => QueryType::PTR,
+            "HINFO" => QueryType::HINFO,
+            "MINFO" => QueryType::MINFO,
+            "MX" => QueryType::MX,
+            "TXT" => QueryType::TXT,
+            "RP" => QueryType::RP,
+            "AFSDB" => QueryType::AFSDB,
+            "X25" => QueryType::X25,
+            "ISDN" => QueryType::ISDN,
+            "RT" => QueryType::RT,
+            "NSAP" => QueryType::NSAP,
+            "NSAP-PTR" => QueryType::NSAPPTR,
+            "SIG" => QueryType::SIG,
+            "KEY" => QueryType::KEY,
+            "PX" => QueryType::PX,
+            "GPOS" => QueryType::GPOS,
+            "AAAA" => QueryType::AAAA,
+            "LOC" => QueryType::LOC,
+            "NXT" => QueryType::NXT,
+            "EID" => QueryType::EID,
+            "NIMLOC" => QueryType::NIMLOC,
+            "SRV" => QueryType::SRV,
+            "ATMA" => QueryType::ATMA,
+            "NAPTR" => QueryType::NAPTR,
+            "KX" => QueryType::KX,
+            "CERT" => QueryType::CERT,
+            "A6" => QueryType::A6,
+            "DNAME" => QueryType::DNAME,
+            "SINK" => QueryType::SINK,
+            "OPT" => QueryType::OPT,
+            "APL" => QueryType::APL,
+            "DS" => QueryType::DS,
+            "SSHFP" => QueryType::SSHFP,
+            "IPSECKEY" => QueryType::IPSECKEY,
+            "RRSIG" => QueryType::RRSIG,
+            "NSEC" => QueryType::NSEC,
+            "DNSKEY" => QueryType::DNSKEY,
+            "DHCID" => QueryType::DHCID,
+            "NSEC3" => QueryType::NSEC3,
+            "NSEC3PARAM" => QueryType::NSEC3PARAM,
+            "TLSA" => QueryType::TLSA,
+            "SMIMEA" => QueryType::SMIMEA,
+            "HIP" => QueryType::HIP,
+            "NINFO" => QueryType::NINFO,
+            "RKEY" => QueryType::RKEY,
+            "TALINK" => QueryType::TALINK,
+            "CDS" => QueryType::CDS,
+            "CDNSKEY" => QueryType::CDNSKEY,
+            "OPENPGPKEY" => QueryType::OPENPGPKEY,
+            "CSYNC" => QueryType::CSYNC,
+            "ZONEMD" => QueryType::ZONEMD,
+            "SVCB" => QueryType::SVCB,
+            "HTTPS" => QueryType::HTTPS,
+            "DSYNC" => QueryType::DSYNC,
+            "HHIT" => QueryType::HHIT,
+            "BRID" => QueryType::BRID,
+            "SPF" => QueryType::SPF,
+            "UINFO" => QueryType::UINFO,
+            "UID" => QueryType::UID,
+            "GID" => QueryType::GID,
+            "UNSPEC" => QueryType::UNSPEC,
+            "NID" => QueryType::NID,
+            "L32" => QueryType::L32,
+            "L64" => QueryType::L64,
+            "LP" => QueryType::LP,
+            "EUI48" => QueryType::EUI48,
+            "EUI64" => QueryType::EUI64,
+            "NXNAME" => QueryType::NXNAME,
+            "TKEY" => QueryType::TKEY,
+            "TSIG" => QueryType::TSIG,
+            "IXFR" => QueryType::IXFR,
+            "AXFR" => QueryType::AXFR,
+            "MAILB" => QueryType::MAILB,
+            "MAILA" => QueryType::MAILA,
+            "ANY" => QueryType::EVERYTHING,
+            "URI" => QueryType::URI,
+            "CAA" => QueryType::CAA,
+            "AVC" => QueryType::AVC,
+            "DOA" => QueryType::DOA,
+            "AMTRELAY" => QueryType::AMTRELAY,
+            "RESINFO" => QueryType::RESINFO,
+            "WALLET" => QueryType::WALLET,
+            "CLA" => QueryType::CLA,
+            "IPN" => QueryType::IPN,
+            "TA" => QueryType::TA,
+            "DLV" => QueryType::DLV,
+            _ => return Err(PresentationError::UnknownType(s.to_string())),
+        };
+
+        Ok(result)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::dns::query_type::QueryType;
+
+    #[test]
+    fn every_type_round_trips() {
+        for num in 0..=u16::MAX {
+            let qtype = QueryType::from_num(num);
+            assert_eq!(qtype.to_num(), num);
+
+            let text = qtype.to_string();
+            assert_eq!(text.parse::<QueryType>().unwrap(), qtype, "{}", text);
+            assert_eq!(text.to_lowercase().parse::<QueryType>().unwrap(), qtype, "{}", text);
+            assert_eq!(format!("TYPE{}", num).parse::<QueryType>().unwrap(), qtype);
+        }
+    }
+}
@@ -1,35 +1,182 @@
+use std::fmt::{Display, Formatter};
+
+/// The response code of a message. The original four bit RCODE of the header
+/// is extended to twelve bits by EDNS(0), which carries the upper eight bits
+/// within the OPT record. Codes which only fit into the extended space can
+/// only be sent in messages which make use of EDNS.
+///
+/// https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-6
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ResultCode {
+    /// Any code not assigned within the registry, kept as its numeric value.
+    UNKNOWN(u16),
     /// No error condition.
-    NOERROR = 0,
+    NOERROR,
     /// Format error - The name server was unable to interpret the query.
-    FORMERR = 1,
+    FORMERR,
     ///  Server failure - The name server was unable to process this query due to
     /// a problem with the name server.
-    SERVFAIL = 2,
+    SERVFAIL,
     /// Name Error - Meaningful only for responses from an authoritative name
     /// server, this code signifies that the domain name referenced in the
     /// query does not exist.
-    NXDOMAIN = 3,
+    NXDOMAIN,
     /// Not Implemented - The name server does not support the requested kind
     /// of query.
-    NOTIMP = 4,
+    NOTIMP,
     /// Refused - The name server refuses to perform the specified operation for
     /// policy reasons.  For example, a name server may not wish to provide the
     /// information to the particular requester, or a name server may not wish to
     /// perform a particular operation (e.g.,  zone transfer) for particular data.
-    REFUSED = 5,
+    REFUSED,
+    /// Some name that ought not to exist, does exist.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc2136#section-2.2
+    YXDOMAIN,
+    /// Some RRset that ought not to exist, does exist.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc2136#section-2.2
+    YXRRSET,
+    /// Some RRset that ought to exist, does not exist.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc2136#section-2.2
+    NXRRSET,
+    /// The server is not authoritative for the zone named in the Zone Section,
+    /// or the request is not authorized.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc8945#section-6
+    NOTAUTH,
+    /// A name used in the Prerequisite or Update Section is not within the zone
+    /// denoted by the Zone Section.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc2136#section-2.2
+    NOTZONE,
+    /// DSO-TYPE Not Implemented.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc8490#section-10.2
+    DSOTYPENI,
+    /// Bad OPT Version, the EDNS version of the request is not implemented.
+    /// Shares its value with BADSIG, the TSIG signature failure.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc6891#section-9
+    BADVERS,
+    /// Key not recognized.
+    BADKEY,
+    /// Signature out of time window.
+    BADTIME,
+    /// Bad TKEY Mode.
+    BADMODE,
+    /// Duplicate key name.
+    BADNAME,
+    /// Algorithm not supported.
+    BADALG,
+    /// Bad Truncation.
+    BADTRUNC,
+    /// Bad/missing Server Cookie.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc7873#section-8
+    BADCOOKIE,
 }
 
 impl ResultCode {
-    pub fn from_num(num: u8) -> ResultCode {
+    pub fn to_num(self) -> u16 {
+        match self {
+            ResultCode::UNKNOWN(x) => x,
+            ResultCode::NOERROR => 0,
+            ResultCode::FORMERR => 1,
+            ResultCode::SERVFAIL => 2,
+            ResultCode::NXDOMAIN => 3,
+            ResultCode::NOTIMP => 4,
+            ResultCode::REFUSED => 5,
+            ResultCode::YXDOMAIN => 6,
+            ResultCode::YXRRSET => 7,
+            ResultCode::NXRRSET => 8,
+            ResultCode::NOTAUTH => 9,
+            ResultCode::NOTZONE => 10,
+            ResultCode::DSOTYPENI => 11,
+            ResultCode::BADVERS => 16,
+            ResultCode::BADKEY => 17,
+            ResultCode::BADTIME => 18,
+            ResultCode::BADMODE => 19,
+            ResultCode::BADNAME => 20,
+            ResultCode::BADALG => 21,
+            ResultCode::BADTRUNC => 22,
+            ResultCode::BADCOOKIE => 23,
+        }
+    }
+
+    pub fn from_num(num: u16) -> ResultCode {
         match num {
+            0 => ResultCode::NOERROR,
             1 => ResultCode::FORMERR,
             2 => ResultCode::SERVFAIL,
             3 => ResultCode::NXDOMAIN,
             4 => ResultCode::NOTIMP,
             5 => ResultCode::REFUSED,
-            0 | _ => ResultCode::NOERROR,
+            6 => ResultCode::YXDOMAIN,
+            7 => ResultCode::YXRRSET,
+            8 => ResultCode::NXRRSET,
+            9 => ResultCode::NOTAUTH,
+            10 => ResultCode::NOTZONE,
+            11 => ResultCode::DSOTYPENI,
+            16 => ResultCode::BADVERS,
+            17 => ResultCode::BADKEY,
+            18 => ResultCode::BADTIME,
+            19 => ResultCode::BADMODE,
+            20 => ResultCode::BADNAME,
+            21 => ResultCode::BADALG,
+            22 => ResultCode::BADTRUNC,
+            23 => ResultCode::BADCOOKIE,
+            _ => ResultCode::UNKNOWN(num),
         }
     }
-}
\ No newline at end of file
+
+    /// Combine the four bit RCODE of the header with the upper eight bits
+    /// carried within the OPT record into the full twelve bit code.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc6891#section-6.1.3
+    pub fn from_parts(header: u8, extended: u8) -> ResultCode {
+        ResultCode::from_num(((extended as u16) << 4) | (header & 0x0F) as u16)
+    }
+
+    /// The lower four bits of the code, which are carried within the header.
+    pub fn header_bits(self) -> u8 {
+        (self.to_num() & 0x0F) as u8
+    }
+
+    /// The upper eight bits of the code, which are carried within the OPT
+    /// record.
+    pub fn extended_bits(self) -> u8 {
+        ((self.to_num() >> 4) & 0xFF) as u8
+    }
+}
+
+impl Display for ResultCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mnemonic = match self {
+            ResultCode::UNKNOWN(x) => return write!(f, "RCODE{}", x),
+            ResultCode::NOERROR => "NOERROR",
+            ResultCode::FORMERR => "FORMERR",
+            ResultCode::SERVFAIL => "SERVFAIL",
+            ResultCode::NXDOMAIN => "NXDOMAIN",
+            ResultCode::NOTIMP => "NOTIMP",
+            ResultCode::REFUSED => "REFUSED",
+            ResultCode::YXDOMAIN => "YXDOMAIN",
+            ResultCode::YXRRSET => "YXRRSET",
+            ResultCode::NXRRSET => "NXRRSET",
+            ResultCode::NOTAUTH => "NOTAUTH",
+            ResultCode::NOTZONE => "NOTZONE",
+            ResultCode::DSOTYPENI => "DSOTYPENI",
+            ResultCode::BADVERS => "BADVERS",
+            ResultCode::BADKEY => "BADKEY",
+            ResultCode::BADTIME => "BADTIME",
+            ResultCode::BADMODE => "BADMODE",
+            ResultCode::BADNAME => "BADNAME",
+            ResultCode::BADALG => "BADALG",
+            ResultCode::BADTRUNC => "BADTRUNC",
+            ResultCode::BADCOOKIE => "BADCOOKIE",
+        };
+
+        write!(f, "{}", mnemonic)
+    }
+}
@@ -0,0 +1,127 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use crate::dns::name::Name;
+
+/// The labels of the domain under which the names of IPv4 addresses are kept.
+///
+/// https://datatracker.ietf.org/doc/html/rfc1035#section-3.5
+const IPV4_REVERSE_DOMAIN: [&str; 2] = ["in-addr", "arpa"];
+
+/// The labels of the domain under which the names of IPv6 addresses are kept.
+///
+/// https://datatracker.ietf.org/doc/html/rfc3596#section-2.5
+const IPV6_REVERSE_DOMAIN: [&str; 2] = ["ip6", "arpa"];
+
+fn reverse_domain(labels: [&str; 2]) -> Name {
+    Name::from_labels(labels.iter().map(|label| label.as_bytes().to_vec()).collect())
+}
+
+/// Convert an address into the name used to look up its PTR records.
+pub fn ip_to_reverse_name(addr: IpAddr) -> Name {
+    match addr {
+        IpAddr::V4(addr) => ipv4_to_reverse_name(addr),
+        IpAddr::V6(addr) => ipv6_to_reverse_name(addr),
+    }
+}
+
+/// The domain name of an IPv4 address is formed from its four octets in
+/// reverse order, followed by `in-addr.arpa`. For example, the address
+/// 10.2.0.52 is represented as `52.0.2.10.in-addr.arpa`.
+///
+/// https://datatracker.ietf.org/doc/html/rfc1035#section-3.5
+pub fn ipv4_to_reverse_name(addr: Ipv4Addr) -> Name {
+    addr.octets().iter()
+        .fold(reverse_domain(IPV4_REVERSE_DOMAIN), |name, octet| name.child(octet.to_string().as_bytes()))
+}
+
+/// The domain name of an IPv6 address is formed from the 32 nibbles of the
+/// address in reverse order, each encoded as a hexadecimal digit, followed by
+/// `ip6.arpa`. For example, the address 4321:0:1:2:3:4:567:89ab is represented
+/// as `b.a.9.8.7.6.5.0.4.0.0.0.3.0.0.0.2.0.0.0.1.0.0.0.0.0.0.0.1.2.3.4.ip6.arpa`.
+///
+/// https://datatracker.ietf.org/doc/html/rfc3596#section-2.5
+pub fn ipv6_to_reverse_name(addr: Ipv6Addr) -> Name {
+    addr.octets().iter()
+        .flat_map(|octet| [octet >> 4, octet & 0x0F])
+        .fold(reverse_domain(IPV6_REVERSE_DOMAIN), |name, nibble| name.child(format!("{:x}", nibble).as_bytes()))
+}
+
+/// Convert a name within `in-addr.arpa` or `ip6.arpa` back into the address it
+/// represents. Names which do not describe a complete address, such as the
+/// name of a reverse zone, result in `None`.
+#[allow(dead_code)]
+pub fn reverse_name_to_ip(name: &Name) -> Option<IpAddr> {
+    let ipv4_domain = reverse_domain(IPV4_REVERSE_DOMAIN);
+    let ipv6_domain = reverse_domain(IPV6_REVERSE_DOMAIN);
+
+    // The labels of the address itself, without the reverse domain.
+    let labels = &name.labels()[..name.label_count().saturating_sub(2)];
+
+    if name.is_subdomain_of(&ipv4_domain) && labels.len() == 4 {
+        let mut octets = [0u8; 4];
+
+        for (index, label) in labels.iter().rev().enumerate() {
+            // Octets are written as plain decimals without leading zeros.
+            if label.is_empty() || !label.iter().all(|x| x.is_ascii_digit()) || (label.len() > 1 && label[0] == b'0') {
+                return None;
+            }
+
+            octets[index] = std::str::from_utf8(label).ok()?.parse().ok()?;
+        }
+
+        return Some(IpAddr::V4(Ipv4Addr::from(octets)));
+    }
+
+    if name.is_subdomain_of(&ipv6_domain) && labels.len() == 32 {
+        let mut octets = [0u8; 16];
+
+        for (index, pair) in labels.rchunks(2).enumerate() {
+            if pair.iter().any(|label| label.len() != 1) {
+                return None;
+            }
+
+            let high = (pair[1][0] as char).to_digit(16)? as u8;
+            let low = (pair[0][0] as char).to_digit(16)? as u8;
+            octets[index] = (high << 4) | low;
+        }
+
+        return Some(IpAddr::V6(Ipv6Addr::from(octets)));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+    use crate::dns::name::Name;
+    use crate::dns::reverse::{ip_to_reverse_name, reverse_name_to_ip};
+
+    fn name(name: &str) -> Name {
+        name.parse().unwrap()
+    }
+
+    #[test]
+    fn ipv4_reverse_name() {
+        let addr: IpAddr = "10.2.0.52".parse().unwrap();
+
+        assert_eq!(ip_to_reverse_name(addr), name("52.0.2.10.in-addr.arpa"));
+        assert_eq!(reverse_name_to_ip(&ip_to_reverse_name(addr)), Some(addr));
+    }
+
+    #[test]
+    fn ipv6_reverse_name() {
+        let addr: IpAddr = "4321:0:1:2:3:4:567:89ab".parse().unwrap();
+
+        assert_eq!(ip_to_reverse_name(addr), name("b.a.9.8.7.6.5.0.4.0.0.0.3.0.0.0.2.0.0.0.1.0.0.0.0.0.0.0.1.2.3.4.ip6.arpa"));
+        assert_eq!(reverse_name_to_ip(&ip_to_reverse_name(addr)), Some(addr));
+    }
+
+    #[test]
+    fn incomplete_reverse_names() {
+        assert_eq!(reverse_name_to_ip(&name("2.10.in-addr.arpa")), None);
+        assert_eq!(reverse_name_to_ip(&name("052.0.2.10.in-addr.arpa")), None);
+        assert_eq!(reverse_name_to_ip(&name("256.0.2.10.in-addr.arpa")), None);
+        assert_eq!(reverse_name_to_ip(&name("1.0.ip6.arpa")), None);
+        assert_eq!(reverse_name_to_ip(&name("52.0.2.10.example.com")), None);
+    }
+}
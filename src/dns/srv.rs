@@ -0,0 +1,140 @@
+use std::net::IpAddr;
+use rand::Rng;
+use crate::dns::dns_record::DnsRecord;
+use crate::dns::name::Name;
+
+/// A single host providing a service, taken from an SRV record along with any
+/// addresses known for it.
+///
+/// https://datatracker.ietf.org/doc/html/rfc2782
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceTarget {
+    pub host: Name,
+    pub port: u16,
+    pub priority: u16,
+    pub weight: u16,
+    pub addrs: Vec<IpAddr>,
+}
+
+impl ServiceTarget {
+    /// Create a target from an SRV record, any other type of record results in
+    /// `None`. The addresses of the target are left empty.
+    pub fn from_record(record: &DnsRecord) -> Option<ServiceTarget> {
+        match record {
+            DnsRecord::SRV { priority, weight, port, host, .. } => Some(ServiceTarget {
+                host: host.clone(),
+                port: *port,
+                priority: *priority,
+                weight: *weight,
+                addrs: Vec::new(),
+            }),
+            _ => None
+        }
+    }
+}
+
+/// Order the targets of a service in the order they should be contacted.
+///
+/// Targets are ordered by ascending priority. Within a single priority, the
+/// order is picked at random, weighted by the weight of each target, as
+/// described within the usage rules of RFC 2782:
+///
+/// To select a target to be contacted next, arrange all SRV RRs (that have not
+/// been ordered yet) in any order, except that all those with weight 0 are
+/// placed at the beginning of the list.
+///
+/// Compute the sum of the weights of those RRs, and with each RR associate the
+/// running sum in the selected order. Then choose a uniform random number
+/// between 0 and the sum computed (inclusive), and select the RR whose running
+/// sum value is the first in the selected order which is greater than or equal
+/// to the random number selected. The target host specified in the selected SRV
+/// RR is the next one to be contacted by the client. Remove this SRV RR from the
+/// set of the unordered SRV RRs and apply the described algorithm to the
+/// unordered SRV RRs to select the next target host. Continue the ordering
+/// process until there are no unordered SRV RRs.
+pub fn order_targets<R: Rng>(mut targets: Vec<ServiceTarget>, rng: &mut R) -> Vec<ServiceTarget> {
+    // Sorting by priority and then weight, both ascending, results in each
+    // priority forming a contiguous group with the zero weights at the front.
+    targets.sort_by_key(|target| (target.priority, target.weight));
+
+    let mut ordered = Vec::with_capacity(targets.len());
+
+    while !targets.is_empty() {
+        let priority = targets[0].priority;
+        let group_size = targets.iter().take_while(|target| target.priority == priority).count();
+        let mut group: Vec<ServiceTarget> = targets.drain(..group_size).collect();
+
+        while !group.is_empty() {
+            let total: u32 = group.iter().map(|target| target.weight as u32).sum();
+            let selected = rng.gen_range(0..=total);
+
+            let mut running_sum = 0;
+            let index = group.iter().position(|target| {
+                running_sum += target.weight as u32;
+                running_sum >= selected
+            }).unwrap_or(0);
+
+            ordered.push(group.remove(index));
+        }
+    }
+
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::dns::srv::{order_targets, ServiceTarget};
+
+    fn target(host: &str, priority: u16, weight: u16) -> ServiceTarget {
+        ServiceTarget { host: host.parse().unwrap(), port: 443, priority, weight, addrs: Vec::new() }
+    }
+
+    fn hosts(targets: &[ServiceTarget]) -> Vec<String> {
+        targets.iter().map(|target| target.host.to_string()).collect()
+    }
+
+    #[test]
+    fn targets_are_ordered_by_priority() {
+        let targets = vec![target("c.example", 20, 0), target("a.example", 0, 5), target("b.example", 10, 5)];
+
+        let ordered = order_targets(targets, &mut StdRng::seed_from_u64(0));
+
+        assert_eq!(hosts(&ordered), ["a.example", "b.example", "c.example"]);
+    }
+
+    #[test]
+    fn targets_of_a_priority_are_picked_by_weight() {
+        let mut first = [0; 2];
+        let mut rng = StdRng::seed_from_u64(1);
+
+        for _ in 0..1000 {
+            let targets = vec![target("light.example", 0, 1), target("heavy.example", 0, 9)];
+            let ordered = order_targets(targets, &mut rng);
+
+            assert_eq!(ordered.len(), 2);
+            first[(ordered[0].host.to_string() == "heavy.example") as usize] += 1;
+        }
+
+        // The heavy target is expected to come first nine times out of ten.
+        assert!(first[1] > 800 && first[1] < 980, "{:?}", first);
+    }
+
+    #[test]
+    fn zero_weights_are_rarely_picked_first() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut zero_first = 0;
+
+        for _ in 0..1000 {
+            let targets = vec![target("zero.example", 0, 0), target("some.example", 0, 100)];
+
+            if order_targets(targets, &mut rng)[0].host.to_string() == "zero.example" {
+                zero_first += 1;
+            }
+        }
+
+        // Only a random number of exactly zero selects the zero weight.
+        assert!(zero_first < 50, "{}", zero_first);
+    }
+}
@@ -1,44 +1,311 @@
+// Record and query type names follow the RFC mnemonics (CNAME, AAAA, NXDOMAIN)
+// rather than Rust casing, so they can be matched against the specifications.
+#![allow(clippy::upper_case_acronyms)]
+
+use std::collections::BTreeMap;
 use std::error::Error;
-use std::net::{Ipv4Addr, UdpSocket};
-use std::process::exit;
-use crate::dns::byte_packet_buffer::{BytePacketBuffer};
+use std::fmt::{Display, Formatter};
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use rand::Rng;
+use crate::dns::byte_packet_buffer::{BytePacketBuffer, MAX_MESSAGE_SIZE, MAX_UDP_MESSAGE_SIZE};
+use crate::dns::build_error::BuildError;
+use crate::dns::byte_packet_buffer_error::BytePacketBufferError;
 use crate::dns::dns_packet::DnsPacket;
-use crate::dns::dns_question::DnsQuestion;
+use crate::dns::dns_packet_view::DnsPacketView;
+use crate::dns::dns_record::DnsRecord;
+use crate::dns::edns::{Edns, DEFAULT_UDP_PAYLOAD_SIZE};
+use crate::dns::name::Name;
+use crate::dns::opcode::Opcode;
+use crate::dns::packet_builder::ResponseBuilder;
+use crate::dns::presentation_error::PresentationError;
 use crate::dns::query_type::QueryType;
-use crate::dns::query_class::QueryClass;
 use crate::dns::result_code::ResultCode;
+use crate::dns::reverse::ip_to_reverse_name;
+use crate::dns::srv::{order_targets, ServiceTarget};
+use crate::server::{read_message, write_message, ServerConfig, Transport};
 
 mod dns;
+mod server;
+
+/// How long to wait for the response to a query sent over UDP.
+const UPSTREAM_UDP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How long a query retried over TCP may take to connect, and then to send
+/// the query or receive the whole response.
+const UPSTREAM_TCP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The number of upstream responses dropped because they did not match the
+/// query they were received for, which may point at a spoofing attempt.
+static MISMATCHED_RESPONSES: AtomicUsize = AtomicUsize::new(0);
+
+/// How long a server found not to preserve the case of the question is sent
+/// the name as given, before 0x20 is tried with it again.
+const CASE_INSENSITIVE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// The most servers remembered as not preserving the case of the question.
+const MAX_CASE_INSENSITIVE_SERVERS: usize = 1024;
+
+/// The upstream servers found not to preserve the case of the question, which
+/// are sent the name as given rather than in random case, along with the time
+/// at which they are to be tried with 0x20 again.
+static CASE_INSENSITIVE_SERVERS: Mutex<BTreeMap<(Ipv4Addr, u16), Instant>> = Mutex::new(BTreeMap::new());
+
+/// The number of upstream responses dropped so far for not matching their
+/// query.
+fn mismatched_responses() -> usize {
+    MISMATCHED_RESPONSES.load(Ordering::Relaxed)
+}
+
+/// Point out any responses dropped while resolving from the command line, as
+/// they may be a sign of someone trying to poison the answer.
+fn report_mismatched_responses() {
+    if mismatched_responses() > 0 {
+        eprintln!("dropped {} responses not matching their query", mismatched_responses());
+    }
+}
+
+/// Query the server for the name, using DNS 0x20 encoding: the letters of
+/// the name are sent in random case, and a response is only accepted if its
+/// question echoes that exact case, as a spoofed response would have to guess
+/// it. Not every server preserves the case of the question, so when a server
+/// answers with the name in another case the query is retried straight away
+/// with the name as given. The server is remembered for a while, and is sent
+/// the name as given until then.
+///
+/// https://datatracker.ietf.org/doc/html/draft-vixie-dnsext-dns0x20-00
+fn lookup(question_name: &Name, question_type: QueryType, server: (Ipv4Addr, u16)) -> Result<DnsPacket, Box<dyn Error>> {
+    if !preserves_case(server) {
+        return send_query(question_name, question_type, server, false);
+    }
+
+    let randomized_name = question_name.with_random_case(&mut rand::thread_rng());
 
+    match send_query(&randomized_name, question_type, server, true) {
+        Ok(mut response) => {
+            // The answer goes back to our own client with the name as it
+            // asked.
+            response.questions[0].q_name = question_name.clone();
+            Ok(response)
+        }
+        Err(e) if e.is::<CaseNotPreserved>() => {
+            println!("{}, retrying without 0x20", e);
 
-fn lookup(question_name: &str, question_type: QueryType, server: (Ipv4Addr, u16)) -> Result<DnsPacket, Box<dyn Error>> {
-    let socket = UdpSocket::bind(("0.0.0.0", 43210))?;
+            if let Ok(mut servers) = CASE_INSENSITIVE_SERVERS.lock() {
+                remember_case_insensitive(&mut servers, server, Instant::now());
+            }
+
+            send_query(question_name, question_type, server, false)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether the server is to be sent the name in random case, which is the
+/// case unless it was recently found not to preserve it.
+fn preserves_case(server: (Ipv4Addr, u16)) -> bool {
+    CASE_INSENSITIVE_SERVERS.lock()
+        .map_or(true, |servers| servers.get(&server).is_none_or(|until| *until <= Instant::now()))
+}
+
+/// Remember a server which doesn't preserve the case of the question. Servers
+/// are forgotten once their time is up, and the one due to be forgotten first
+/// makes room when too many are remembered.
+fn remember_case_insensitive(servers: &mut BTreeMap<(Ipv4Addr, u16), Instant>, server: (Ipv4Addr, u16), now: Instant) {
+    servers.retain(|_, until| *until > now);
+
+    if servers.len() >= MAX_CASE_INSENSITIVE_SERVERS && !servers.contains_key(&server) {
+        if let Some(first) = servers.iter().min_by_key(|(_, until)| **until).map(|(server, _)| *server) {
+            servers.remove(&first);
+        }
+    }
 
-    let mut packet = DnsPacket::new();
+    servers.insert(server, now + CASE_INSENSITIVE_TTL);
+}
 
-    packet.header.id = 6666;
-    packet.header.questions = 1;
-    packet.header.recursion_desired = false;
+/// The error of a query answered with the name in another case than it was
+/// sent in, by a server which doesn't preserve it.
+#[derive(Debug)]
+struct CaseNotPreserved(Ipv4Addr);
 
-    let question = DnsQuestion::new(
-        question_name.to_string(),
-        question_type,
-        QueryClass::IN);
+impl Display for CaseNotPreserved {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} answered without preserving the case of the question", self.0)
+    }
+}
 
-    packet.questions.push(question);
+impl Error for CaseNotPreserved {}
+
+/// Send a query over UDP and wait for the response to it, falling back to TCP
+/// when the response doesn't fit.
+///
+/// Each query is sent from a random port with a random id, and only a response
+/// from the server which carries the same id and question is accepted. Any
+/// other datagram arriving on the port is dropped and counted, as an attacker
+/// would have to guess all of these to get a forged response accepted. Without
+/// an acceptable response, the query fails with a `TimedOut` error.
+///
+/// When `match_case` is set, a response with the name in another case fails
+/// the query with a `CaseNotPreserved` error instead.
+///
+/// https://datatracker.ietf.org/doc/html/rfc5452#section-9
+fn send_query(question_name: &Name, question_type: QueryType, server: (Ipv4Addr, u16), match_case: bool) -> Result<DnsPacket, Box<dyn Error>> {
+    // Every query gets a socket of its own, which also allows many lookups to
+    // run at once.
+    let socket = bind_random_port()?;
+
+    // Advertise that we can handle responses larger than 512 bytes, which
+    // saves the upstream server from having to truncate larger answers.
+    let packet = DnsPacket::query(question_name.clone(), question_type)
+        .recursion_desired(false)
+        .edns(Edns::new(DEFAULT_UDP_PAYLOAD_SIZE))
+        .build();
 
     let mut request_buffer = BytePacketBuffer::new();
 
-    packet.write(&mut request_buffer)?;
-    socket.send_to(&request_buffer.buffer[0..request_buffer.position], server)?;
+    packet.write(&mut request_buffer, MAX_UDP_MESSAGE_SIZE)?;
+    let request = request_buffer.written();
+    socket.send_to(request, server)?;
 
-    let mut result_buffer = BytePacketBuffer::new();
-    socket.recv_from(&mut result_buffer.buffer)?;
+    // The upstream server may well answer with more than 512 bytes, so the
+    // buffer is sized for the largest possible message and then cut down to
+    // the data actually received.
+    let mut received = vec![0; MAX_MESSAGE_SIZE];
+
+    // Datagrams which don't match are skipped over, but don't extend the time
+    // the response has to arrive in. An upstream server which never answers
+    // only ties up the lookup for as long as the timeout.
+    let deadline = Instant::now() + UPSTREAM_UDP_TIMEOUT;
+
+    let size = loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(ErrorKind::TimedOut, format!("no response from {}", server.0)).into());
+        }
+
+        socket.set_read_timeout(Some(remaining))?;
+        let (size, src) = match socket.recv_from(&mut received) {
+            Ok(received) => received,
+            // The deadline is checked again before waiting any longer.
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        if src == SocketAddr::from(server) {
+            match match_query(&received[..size], &packet) {
+                QueryMatch::Exact => break size,
+                QueryMatch::OtherCase if !match_case => break size,
+                // Getting everything but the case right takes guessing the
+                // port and id, so this is the server rather than a forgery.
+                QueryMatch::OtherCase => return Err(CaseNotPreserved(server.0).into()),
+                QueryMatch::Mismatch => {}
+            }
+        }
 
-    Ok(DnsPacket::from_buffer(&mut result_buffer)?)
+        let mismatched = MISMATCHED_RESPONSES.fetch_add(1, Ordering::Relaxed) + 1;
+        println!("dropped a datagram from {} not matching the query to {} ({} so far)", src, server.0, mismatched);
+    };
+
+    let mut result_buffer = BytePacketBuffer::from_bytes(&received[..size]);
+
+    // A response which didn't fit is retried over TCP, whether the server
+    // said so with the TC bit or the datagram was simply cut short.
+    match DnsPacket::from_buffer(&mut result_buffer) {
+        Ok(response) if !response.header.truncated_message => return Ok(response),
+        Ok(_) => println!("response from {} was truncated, retrying over TCP", server.0),
+        Err(e) if matches!(e.error, BytePacketBufferError::EndOfBuffer) => {
+            println!("response from {} was incomplete ({}), retrying over TCP", server.0, e)
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    // A failure over TCP is reported as it is, rather than being taken for
+    // the query going unanswered.
+    send_query_tcp(request, &packet, server)
+        .map_err(|e| format!("retry over TCP to {} failed: {}", server.0, e).into())
 }
 
-fn recursive_lookup(question_name: &str, question_type: QueryType) -> Result<DnsPacket, Box<dyn Error>> {
+/// Send an already encoded query over TCP and wait for the response, as
+/// described by RFC 7766.
+///
+/// https://datatracker.ietf.org/doc/html/rfc7766
+fn send_query_tcp(request: &[u8], query: &DnsPacket, server: (Ipv4Addr, u16)) -> Result<DnsPacket, Box<dyn Error>> {
+    let mut stream = TcpStream::connect_timeout(&SocketAddr::from(server), UPSTREAM_TCP_TIMEOUT)?;
+    stream.set_write_timeout(Some(UPSTREAM_TCP_TIMEOUT))?;
+
+    write_message(&mut stream, request)?;
+    let response = read_message(&mut stream, Instant::now() + UPSTREAM_TCP_TIMEOUT)?;
+
+    // A response over TCP can't be forged by an attacker who isn't on the
+    // path, so the case of its name is not held against it.
+    if match_query(&response, query) == QueryMatch::Mismatch {
+        MISMATCHED_RESPONSES.fetch_add(1, Ordering::Relaxed);
+        return Err(format!("response from {} over TCP does not match the query", server.0).into());
+    }
+
+    Ok(DnsPacket::from_buffer(&mut BytePacketBuffer::from_bytes(&response))?)
+}
+
+/// Bind a UDP socket to a port picked at random, rather than leaving it to the
+/// system which may well hand ports out in order. A port which is already in
+/// use is simply swapped for another.
+///
+/// https://datatracker.ietf.org/doc/html/rfc5452#section-9.2
+fn bind_random_port() -> io::Result<UdpSocket> {
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..10 {
+        match UdpSocket::bind(("0.0.0.0", rng.gen_range(1024..=u16::MAX))) {
+            Err(e) if e.kind() == ErrorKind::AddrInUse => continue,
+            result => return result,
+        }
+    }
+
+    UdpSocket::bind(("0.0.0.0", 0))
+}
+
+/// How a message relates to the query it was received for.
+#[derive(Debug, PartialEq, Eq)]
+enum QueryMatch {
+    /// The response carries the id and the single question of the query.
+    Exact,
+    /// As `Exact`, except for the case of the name.
+    OtherCase,
+    /// The message is not a response to the query.
+    Mismatch,
+}
+
+/// Check whether the message is a response to the query: it must carry the
+/// same id and the same single question. Only the header and question are
+/// read, so a datagram which doesn't match is never parsed in full.
+fn match_query(response: &[u8], query: &DnsPacket) -> QueryMatch {
+    let Ok(response) = DnsPacketView::new(response) else {
+        return QueryMatch::Mismatch;
+    };
+
+    if !response.header().response || response.header().id != query.header.id {
+        return QueryMatch::Mismatch;
+    }
+
+    let mut questions = response.questions();
+
+    match (questions.next(), questions.next(), query.questions.first()) {
+        (Some(Ok(question)), None, Some(expected)) if question.q_type == expected.q_type && question.q_class == expected.q_class => {
+            match question.name.to_name() {
+                Ok(name) if name.eq_case_sensitive(&expected.q_name) => QueryMatch::Exact,
+                Ok(name) if name == expected.q_name => QueryMatch::OtherCase,
+                _ => QueryMatch::Mismatch,
+            }
+        }
+        _ => QueryMatch::Mismatch,
+    }
+}
+
+fn recursive_lookup(question_name: &Name, question_type: QueryType) -> Result<DnsPacket, Box<dyn Error>> {
     // For now we're always starting with *a.root-servers.net*.
     //
     // There are 13 root servers, but in reality many more. You can read more
@@ -51,7 +318,7 @@ fn recursive_lookup(question_name: &str, question_type: QueryType) -> Result<Dns
 
     // Since it might take an arbitrary number of steps, we enter an unbounded loop.
     loop {
-        println!("attempting lookup of {:?} {} with ns {}",
+        println!("attempting lookup of {} {} with ns {}",
                  question_type, question_name, ns);
 
         // The next step is to send the query to the active server.
@@ -89,7 +356,7 @@ fn recursive_lookup(question_name: &str, question_type: QueryType) -> Result<Dns
         // Here we go down the rabbit hole by starting _another_ lookup sequence in the
         // midst of our current one. Hopefully, this will give us the IP of an appropriate
         // name server.
-        let recursive_response = recursive_lookup(&new_ns_name, QueryType::A)?;
+        let recursive_response = recursive_lookup(new_ns_name, QueryType::A)?;
 
         // Finally, we pick a random ip from the result, and restart the loop.
         // If no such record is available, we again return the last result we
@@ -102,90 +369,589 @@ fn recursive_lookup(question_name: &str, question_type: QueryType) -> Result<Dns
     }
 }
 
-/// handle a single incoming packet request.
-fn handle_query(socket: &UdpSocket) -> Result<(), Box<dyn Error>> {
-    // With a socket ready, we can go ahead and read a packet. This will block
-    // until one is received.
-    let mut request_buffer = BytePacketBuffer::new();
+/// Resolve the host names of an address by looking up the PTR records of its
+/// name within `in-addr.arpa` or `ip6.arpa`. An address without any PTR
+/// records results in an empty list.
+fn reverse_lookup(addr: IpAddr) -> Result<Vec<Name>, Box<dyn Error>> {
+    let response = recursive_lookup(&ip_to_reverse_name(addr), QueryType::PTR)?;
+
+    // Classless delegations (RFC 2317) answer with a CNAME leading to the PTR
+    // record, so every PTR record in the answer is taken regardless of its
+    // owner name.
+    let hosts = response.answers.into_iter().filter_map(|record| match record {
+        DnsRecord::PTR { host, .. } => Some(host),
+        _ => None
+    }).collect();
+
+    Ok(hosts)
+}
 
-    // The `recv_from` function will write the data into the provided buffer,
-    // and will return the length of the data read as well as the source
-    // address.
-    //
-    // We're not interested in the length, but we need to keep track of the
-    // source in order to send our reply later on.
-    let (_, src) = socket.recv_from(&mut request_buffer.buffer)?;
+/// Resolve a service name of the form `_service._proto.name` into the hosts
+/// providing it, in the order they should be contacted as described by
+/// RFC 2782. The addresses of each host are taken from the additional section
+/// when present, otherwise they are looked up separately.
+fn resolve_service(service_name: &Name) -> Result<Vec<ServiceTarget>, Box<dyn Error>> {
+    let response = recursive_lookup(service_name, QueryType::SRV)?;
+
+    let mut targets: Vec<ServiceTarget> = response.answers.iter()
+        .filter_map(ServiceTarget::from_record)
+        .collect();
+
+    // A single target of "." means that the service is decidedly not
+    // available at this domain.
+    if targets.len() == 1 && targets[0].host.is_root() {
+        return Ok(Vec::new());
+    }
 
-    // Next, `DnsPacket::from_buffer` is used ot parse the raw bytes into a
-    // `DnsPacket`.
-    let mut incoming_request = DnsPacket::from_buffer(&mut request_buffer)?;
+    for target in targets.iter_mut() {
+        target.addrs = response.get_addresses(&target.host);
 
-    let mut packet = DnsPacket::new();
+        if target.addrs.is_empty() {
+            // A failure to resolve a single target should not fail the service
+            // as a whole, the remaining targets might well be reachable.
+            for query_type in [QueryType::A, QueryType::AAAA] {
+                if let Ok(result) = recursive_lookup(&target.host, query_type) {
+                    target.addrs.extend(result.get_addresses(&target.host));
+                }
+            }
+        }
+    }
 
-    packet.header.id = incoming_request.header.id;
-    packet.header.recursion_desired = true;
-    packet.header.recursion_available = true;
-    packet.header.response = true;
+    Ok(order_targets(targets, &mut rand::thread_rng()))
+}
 
-    // In the normal case, exactly one question is present.
-    if let Some(question) = incoming_request.questions.pop() {
-        println!("received query: {:?}", question);
+/// Copy the response code and records of an upstream response into our own
+/// response. Records in a section they don't belong in are left out, rather
+/// than failing the whole response, while a response code which can't be
+/// carried is an error.
+fn relay(response: &mut ResponseBuilder, result: DnsPacket) -> Result<(), BuildError> {
+    response.rcode(result.header.rescode)?;
+
+    // The SOA record of a negative answer (NXDOMAIN, or NODATA when there
+    // are no answers) is passed on with the TTL the answer may be cached
+    // for, as described in RFC 2308 section 3.
+    let negative = result.header.rescode == ResultCode::NXDOMAIN || result.answers.is_empty();
+    let negative_ttl = if negative { result.get_negative_ttl() } else { None };
+
+    for rec in result.answers {
+        println!("Answer: {}", rec);
+        if let Err(e) = response.answer(rec) {
+            println!("left out of the answer: {}", e);
+        }
+    }
+    for mut rec in result.authorities {
+        if let (DnsRecord::SOA { ttl, .. }, Some(negative_ttl)) = (&mut rec, negative_ttl) {
+            *ttl = negative_ttl;
+        }
+
+        println!("Authority: {}", rec);
+        if let Err(e) = response.authority(rec) {
+            println!("left out of the answer: {}", e);
+        }
+    }
+    for rec in result.resources {
+        println!("Resource: {}", rec);
+        if let Err(e) = response.additional(rec) {
+            println!("left out of the answer: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Answer a single request, whichever transport it arrived over, returning the
+/// encoded response.
+fn handle_request(request: &[u8], transport: Transport) -> Result<Vec<u8>, Box<dyn Error>> {
+    // `DnsPacketView::new` is used to look into the raw bytes. Only the
+    // header, the first question and the EDNS information are ever needed,
+    // which are read without parsing the whole request into a `DnsPacket`.
+    let request = DnsPacketView::new(request)?;
+    let request_header = request.header();
+    let request_edns = request.edns()?;
+
+    // The response starts out as a copy of the id, opcode and question of the
+    // request, to which the answer is then added.
+    let mut response = request.response()?;
+    response.recursion_available(true);
+
+    // When the client made use of EDNS, it is echoed back in the response, and
+    // a UDP response may then use up to the payload size the client
+    // advertised (but no more than we are willing to send). Otherwise, the
+    // classic 512 byte limit applies. Over TCP, the only limit is the two byte
+    // length prefix of the message.
+    let mut max_size = match transport {
+        Transport::Udp => MAX_UDP_MESSAGE_SIZE,
+        Transport::Tcp => MAX_MESSAGE_SIZE,
+    };
+
+    if let Some(edns) = &request_edns {
+        response.edns(Edns::new(DEFAULT_UDP_PAYLOAD_SIZE));
+
+        if transport == Transport::Udp {
+            max_size = (edns.udp_payload_size.min(DEFAULT_UDP_PAYLOAD_SIZE) as usize).max(MAX_UDP_MESSAGE_SIZE);
+        }
+    }
+
+    if request_header.opcode != Opcode::QUERY {
+        // Only standard queries are supported, any other kind of request such
+        // as a NOTIFY or UPDATE is refused as not implemented.
+        response.rcode(ResultCode::NOTIMP)?;
+    } else if request_edns.as_ref().is_some_and(|edns| edns.version > 0) {
+        // Only version 0 of EDNS exists, any later version must be answered
+        // with BADVERS and the highest version we do support.
+        //
+        // https://datatracker.ietf.org/doc/html/rfc6891#section-6.1.3
+        response.rcode(ResultCode::BADVERS)?;
+    } else if let Some(question) = response.question().cloned() {
+        // In the normal case, exactly one question is present.
+        println!("received query: {}", question);
 
         // Since all is set up and as expected, the query can be forwarded to
         // the target server. There's always the possibility that the query will
         // fail, in which case, the `ServerFailed` response code will be set to
         // indicate as much to the client.
         //
-        // If rather everything goes as planned, the question and response
-        // records are copied into our response packet.
-        if let Ok(result) = recursive_lookup(&question.q_name, question.q_type.clone()) {
-            packet.questions.push(question);
-            packet.header.rescode = result.header.rescode;
-
-            for rec in result.answers {
-                println!("Answer: {:?}", rec);
-                packet.answers.push(rec);
+        // If rather everything goes as planned, the response records are
+        // copied into our response packet.
+        let mut answer = response.clone();
+        let answered = recursive_lookup(&question.q_name, question.q_type)
+            .and_then(|result| Ok(relay(&mut answer, result)?));
+
+        match answered {
+            Ok(()) => response = answer,
+            Err(_) => {
+                response.rcode(ResultCode::SERVFAIL)?;
             }
-            for rec in result.authorities {
-                println!("Authority: {:?}", rec);
-                packet.authorities.push(rec);
-            }
-            for rec in result.resources {
-                println!("Resource: {:?}", rec);
-                packet.resources.push(rec);
-            }
-        } else {
-            packet.header.rescode = ResultCode::SERVFAIL;
         }
     } else {
         // Being mindful of how unreliable input data from arbitrary senders can
         // be, we need make sure that a question is actually present. If not, we
         // return `FORMERR` to indicate that the sender made something wrong.
-        packet.header.rescode = ResultCode::FORMERR;
+        response.rcode(ResultCode::FORMERR)?;
     }
 
     // The only thing remaining is to encode our response and send it off!
+    // Should the answer not fit, it is truncated with the TC bit set, and the
+    // client is expected to retry over TCP.
+    let packet = response.build();
     let mut result_buffer = BytePacketBuffer::new();
-    packet.write(&mut result_buffer)?;
+    packet.write(&mut result_buffer, max_size)?;
 
-    let data = result_buffer.get_range(0, result_buffer.position())?;
-    socket.send_to(data, src)?;
-
-    Ok(())
+    Ok(result_buffer.written().to_vec())
 }
 
 
 fn main() -> Result<(), Box<dyn Error>> {
-    // Bind an UDP socket on port 2053
-    let socket = UdpSocket::bind(("0.0.0.0", 2053))?;
+    let mut args = std::env::args().skip(1);
 
-    // For now, queries are handled sequentially, so an infinite loop for servicing
-    // requests is initiated.
-    loop {
-        match handle_query(&socket) {
-            Ok(_) => {}
-            Err(e) => eprintln!("An error occurred: {}", e),
+    match args.next().as_deref() {
+        // Rather than serving, look up the host names of a single address,
+        // such as `dns-server reverse 8.8.8.8`.
+        Some("reverse") => {
+            let addr: IpAddr = args.next().ok_or("usage: dns-server reverse <address>")?.parse()?;
+
+            for host in reverse_lookup(addr)? {
+                println!("{}", show_name(&host));
+            }
+
+            report_mismatched_responses();
+            Ok(())
         }
+        // Or list the hosts providing a service in the order they should be
+        // contacted, such as `dns-server srv _imaps._tcp.gmail.com`.
+        Some("srv") => {
+            let name = parse_name(&args.next().ok_or("usage: dns-server srv <_service._proto.name>")?)?;
+
+            for target in resolve_service(&name)? {
+                println!("{} {} {} {} {:?}", target.priority, target.weight, target.port, show_name(&target.host), target.addrs);
+            }
+
+            report_mismatched_responses();
+            Ok(())
+        }
+        // Or print the text records of a name, each joined into a single
+        // value, such as `dns-server txt gmail.com`.
+        Some("txt") => {
+            let name = parse_name(&args.next().ok_or("usage: dns-server txt <name>")?)?;
+
+            let response = recursive_lookup(&name, QueryType::TXT)?;
+            for value in response.answers.iter().filter_map(DnsRecord::txt_string) {
+                println!("{}", value);
+            }
+
+            report_mismatched_responses();
+            Ok(())
+        }
+        // Anything else is taken as the options of the server, such as
+        // `dns-server --port 53`.
+        _ => serve(ServerConfig::from_args(std::env::args().skip(1))?),
     }
 }
 
+/// Names given on the command line may be internationalized, and are shown in
+/// Unicode, when built with the `idna` feature.
+#[cfg(feature = "idna")]
+fn parse_name(text: &str) -> Result<Name, PresentationError> {
+    Name::from_unicode(text)
+}
+
+#[cfg(not(feature = "idna"))]
+fn parse_name(text: &str) -> Result<Name, PresentationError> {
+    text.parse()
+}
+
+#[cfg(feature = "idna")]
+fn show_name(name: &Name) -> String {
+    name.to_unicode()
+}
+
+#[cfg(not(feature = "idna"))]
+fn show_name(name: &Name) -> String {
+    name.to_string()
+}
+
+fn serve(config: ServerConfig) -> Result<(), Box<dyn Error>> {
+    // Bind an UDP socket and a TCP listener, both on port 2053 unless told
+    // otherwise
+    let socket = UdpSocket::bind(("0.0.0.0", config.port))?;
+    let listener = TcpListener::bind(("0.0.0.0", config.port))?;
+
+    // TCP connections are accepted on a thread of their own, and each
+    // connection is then served on its own thread as well.
+    let tcp_config = config.clone();
+    thread::spawn(move || server::serve_tcp(listener, &tcp_config, handle_request));
+
+    // UDP queries are received here and answered by a pool of workers, so a
+    // slow lookup only holds up the worker handling it.
+    server::serve_udp(socket, &config, handle_request)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::io::{self, ErrorKind};
+    use std::net::{Ipv4Addr, TcpListener, UdpSocket};
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use crate::dns::byte_packet_buffer::{BytePacketBuffer, MAX_MESSAGE_SIZE};
+    use crate::dns::dns_packet::DnsPacket;
+    use crate::dns::dns_record::DnsRecord;
+    use crate::dns::edns::{Edns, DEFAULT_UDP_PAYLOAD_SIZE};
+    use crate::dns::name::Name;
+    use crate::dns::query_type::QueryType;
+    use crate::dns::result_code::ResultCode;
+    use crate::dns::opcode::Opcode;
+    use crate::server::{read_message, write_message, Transport};
+    use crate::{handle_request, lookup, mismatched_responses, preserves_case, relay, remember_case_insensitive, send_query};
+    use crate::{CASE_INSENSITIVE_TTL, MAX_CASE_INSENSITIVE_SERVERS};
+
+    // Tests which count dropped datagrams run one at a time, so that none of
+    // them sees the datagrams dropped by another.
+    static COUNTING: Mutex<()> = Mutex::new(());
+
+    fn encode(packet: &DnsPacket) -> Vec<u8> {
+        let mut buffer = BytePacketBuffer::new();
+        packet.write(&mut buffer, MAX_MESSAGE_SIZE).unwrap();
+        buffer.written().to_vec()
+    }
+
+    // Answer each of the given number of queries with the responses made by
+    // `respond` from the query, which are all sent in turn.
+    fn fake_server(queries: usize, respond: fn(&DnsPacket) -> Vec<DnsPacket>) -> (Ipv4Addr, u16) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+
+        thread::spawn(move || {
+            for _ in 0..queries {
+                let mut received = [0; MAX_MESSAGE_SIZE];
+                let (size, src) = socket.recv_from(&mut received).unwrap();
+
+                let query = DnsPacket::from_buffer(&mut BytePacketBuffer::from_bytes(&received[..size])).unwrap();
+                for response in respond(&query) {
+                    socket.send_to(&encode(&response), src).unwrap();
+                }
+            }
+        });
+
+        (Ipv4Addr::LOCALHOST, port)
+    }
+
+    // Answer a single query over UDP with the message made by `udp` from the
+    // query, and then the same query over TCP with the full answer.
+    fn fake_server_with_tcp(udp: fn(&DnsPacket) -> Vec<u8>) -> (Ipv4Addr, u16) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).unwrap();
+
+        thread::spawn(move || {
+            let mut received = [0; MAX_MESSAGE_SIZE];
+            let (size, src) = socket.recv_from(&mut received).unwrap();
+
+            let query = DnsPacket::from_buffer(&mut BytePacketBuffer::from_bytes(&received[..size])).unwrap();
+            socket.send_to(&udp(&query), src).unwrap();
+
+            let (mut stream, _) = listener.accept().unwrap();
+            let request = read_message(&mut stream, Instant::now() + Duration::from_secs(5)).unwrap();
+
+            let query = DnsPacket::from_buffer(&mut BytePacketBuffer::from_bytes(&request)).unwrap();
+            write_message(&mut stream, &encode(&answer(&query, false))).unwrap();
+        });
+
+        (Ipv4Addr::LOCALHOST, port)
+    }
+
+    fn answer(query: &DnsPacket, lowercase: bool) -> DnsPacket {
+        let mut response = query.clone();
+        response.header.response = true;
+        response.edns = None;
+
+        let name = &mut response.questions[0].q_name;
+        if lowercase {
+            *name = name.to_string().to_lowercase().parse().unwrap();
+        }
+
+        response.answers.push(DnsRecord::A { domain: name.clone(), addr: Ipv4Addr::new(10, 0, 0, 1), ttl: 300 });
+        response
+    }
+
+    fn name() -> Name {
+        // Enough letters that the random case is never all lowercase.
+        "case-preservation.example.com".parse().unwrap()
+    }
+
+    fn soa(ttl: u32, minimum: u32) -> DnsRecord {
+        DnsRecord::SOA {
+            domain: "example.com".parse().unwrap(),
+            mname: "ns1.example.com".parse().unwrap(),
+            rname: "hostmaster.example.com".parse().unwrap(),
+            serial: 1,
+            refresh: 7200,
+            retry: 900,
+            expire: 1209600,
+            minimum,
+            ttl,
+        }
+    }
+
+    #[test]
+    fn responses_in_another_case_are_retried_without_0x20() {
+        let _counting = COUNTING.lock().unwrap_or_else(|e| e.into_inner());
+
+        // The server answers every query with the name in lowercase.
+        let server = fake_server(3, |query| vec![answer(query, true)]);
+        let mismatched = mismatched_responses();
+
+        // The first lookup is retried with the name as given as soon as the
+        // answer arrives, without counting it as a forgery.
+        let start = Instant::now();
+        let response = lookup(&name(), QueryType::A, server).unwrap();
+
+        assert_eq!(response.answers.len(), 1);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(mismatched_responses(), mismatched);
+        assert!(!preserves_case(server));
+
+        // Every later lookup goes straight to the name as given, as the
+        // server only answers one more query.
+        let response = lookup(&name(), QueryType::A, server).unwrap();
+        assert_eq!(response.answers.len(), 1);
+    }
+
+    #[test]
+    fn datagrams_not_matching_the_query_are_dropped() {
+        let _counting = COUNTING.lock().unwrap_or_else(|e| e.into_inner());
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+
+        thread::spawn(move || {
+            let mut received = [0; MAX_MESSAGE_SIZE];
+            let (size, src) = socket.recv_from(&mut received).unwrap();
+            let query = DnsPacket::from_buffer(&mut BytePacketBuffer::from_bytes(&received[..size])).unwrap();
+
+            let mut wrong_id = answer(&query, false);
+            wrong_id.header.id = query.header.id.wrapping_add(1);
+
+            let mut no_question = answer(&query, false);
+            no_question.questions.clear();
+
+            let mut two_questions = answer(&query, false);
+            two_questions.questions.push(two_questions.questions[0].clone());
+
+            for response in [wrong_id, no_question, two_questions] {
+                socket.send_to(&encode(&response), src).unwrap();
+            }
+
+            // The right answer, from the wrong address.
+            let other = UdpSocket::bind("127.0.0.1:0").unwrap();
+            other.send_to(&encode(&answer(&query, false)), src).unwrap();
+
+            socket.send_to(&encode(&answer(&query, false)), src).unwrap();
+        });
+
+        let mismatched = mismatched_responses();
+        let response = send_query(&name(), QueryType::A, (Ipv4Addr::LOCALHOST, port), true).unwrap();
+
+        assert_eq!(response.answers.len(), 1);
+        assert_eq!(response.questions.len(), 1);
+        assert_eq!(mismatched_responses() - mismatched, 4);
+    }
+
+    #[test]
+    fn servers_which_do_not_answer_keep_0x20() {
+        let server = fake_server(1, |_| Vec::new());
+
+        let error = lookup(&name(), QueryType::A, server).unwrap_err();
+
+        assert!(error.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == ErrorKind::TimedOut));
+        assert!(preserves_case(server));
+    }
+
+    #[test]
+    fn case_insensitive_servers_are_forgotten() {
+        let mut servers = BTreeMap::new();
+        let now = Instant::now();
+
+        for port in 0..MAX_CASE_INSENSITIVE_SERVERS as u16 {
+            remember_case_insensitive(&mut servers, (Ipv4Addr::LOCALHOST, port), now + Duration::from_millis(port as u64));
+        }
+        assert_eq!(servers.len(), MAX_CASE_INSENSITIVE_SERVERS);
+
+        // The server remembered first makes room for the next one.
+        remember_case_insensitive(&mut servers, (Ipv4Addr::LOCALHOST, 60000), now + Duration::from_secs(2));
+        assert_eq!(servers.len(), MAX_CASE_INSENSITIVE_SERVERS);
+        assert!(!servers.contains_key(&(Ipv4Addr::LOCALHOST, 0)));
+        assert!(servers.contains_key(&(Ipv4Addr::LOCALHOST, 60000)));
+
+        // Once their time is up, servers are forgotten.
+        remember_case_insensitive(&mut servers, (Ipv4Addr::LOCALHOST, 60001), now + CASE_INSENSITIVE_TTL + Duration::from_secs(3));
+        assert_eq!(servers.keys().collect::<Vec<_>>(), [&(Ipv4Addr::LOCALHOST, 60001)]);
+    }
+
+    #[test]
+    fn truncated_responses_are_retried_over_tcp() {
+        let server = fake_server_with_tcp(|query| {
+            let mut response = answer(query, false);
+            response.answers.clear();
+            response.header.truncated_message = true;
+
+            encode(&response)
+        });
+
+        let response = lookup(&name(), QueryType::A, server).unwrap();
+
+        assert!(!response.header.truncated_message);
+        assert_eq!(response.answers.len(), 1);
+    }
+
+    #[test]
+    fn incomplete_responses_are_retried_over_tcp() {
+        // The datagram is cut short in the middle of the answer, without the
+        // TC bit being set.
+        let server = fake_server_with_tcp(|query| {
+            let message = encode(&answer(query, false));
+            message[..message.len() - 2].to_vec()
+        });
+
+        let response = lookup(&name(), QueryType::A, server).unwrap();
+
+        assert!(!response.header.truncated_message);
+        assert_eq!(response.answers.len(), 1);
+    }
+
+    #[test]
+    fn unexpected_upstream_records_are_left_out() {
+        let query = DnsPacket::query(name(), QueryType::A).build();
+        let mut upstream = answer(&query, false);
+        upstream.header.rescode = ResultCode::NXDOMAIN;
+        upstream.authorities.push(DnsRecord::CNAME { domain: name(), host: "elsewhere.example.com".parse().unwrap(), ttl: 300 });
+        upstream.authorities.push(DnsRecord::NS { domain: name(), host: "ns1.example.com".parse().unwrap(), ttl: 300 });
+        upstream.authorities.push(upstream.answers[0].clone());
+
+        let mut response = DnsPacket::response_to(&query);
+        relay(&mut response, upstream.clone()).unwrap();
+
+        let packet = response.build();
+        assert_eq!(packet.header.rescode, ResultCode::NXDOMAIN);
+        assert_eq!(packet.answers, upstream.answers);
+        assert_eq!(packet.authorities, vec![upstream.authorities[1].clone()]);
+    }
+
+    #[test]
+    fn negative_answers_are_relayed_with_the_negative_ttl() {
+        let query = DnsPacket::query(name(), QueryType::A).build();
+        let mut upstream = answer(&query, false);
+        upstream.answers.clear();
+        upstream.authorities.push(soa(3600, 300));
+
+        let mut response = DnsPacket::response_to(&query);
+        relay(&mut response, upstream.clone()).unwrap();
+        assert_eq!(response.build().authorities, vec![soa(300, 300)]);
+
+        upstream.header.rescode = ResultCode::NXDOMAIN;
+        upstream.authorities = vec![soa(60, 300)];
+
+        let mut response = DnsPacket::response_to(&query);
+        relay(&mut response, upstream).unwrap();
+        assert_eq!(response.build().authorities, vec![soa(60, 300)]);
+    }
+
+    #[test]
+    fn extended_upstream_rcodes_need_edns() {
+        let query = DnsPacket::query(name(), QueryType::A).build();
+        let mut upstream = answer(&query, false);
+        upstream.header.rescode = ResultCode::BADCOOKIE;
+
+        assert!(relay(&mut DnsPacket::response_to(&query), upstream).is_err());
+    }
+
+    #[test]
+    fn requests_are_answered_without_a_lookup_when_refused() {
+        let mut query = DnsPacket::query(name(), QueryType::A).build();
+        query.header.opcode = Opcode::NOTIFY;
+
+        let response = handle_request(&encode(&query), Transport::Udp).unwrap();
+        let response = DnsPacket::from_buffer(&mut BytePacketBuffer::from_bytes(&response)).unwrap();
+
+        assert_eq!(response.header.id, query.header.id);
+        assert_eq!(response.header.rescode, ResultCode::NOTIMP);
+        assert_eq!(response.questions, query.questions);
+    }
+
+    fn request(opcode: Opcode, edns: Option<Edns>) -> DnsPacket {
+        let mut query = DnsPacket::query(name(), QueryType::A).build();
+        query.header.opcode = opcode;
+        query.edns = edns;
+
+        query
+    }
+
+    fn handle(request: &DnsPacket) -> DnsPacket {
+        let response = handle_request(&encode(request), Transport::Udp).unwrap();
+        DnsPacket::from_buffer(&mut BytePacketBuffer::from_bytes(&response)).unwrap()
+    }
+
+    #[test]
+    fn edns_is_echoed_back() {
+        let response = handle(&request(Opcode::NOTIFY, Some(Edns::new(4096))));
+        assert_eq!(response.edns, Some(Edns::new(DEFAULT_UDP_PAYLOAD_SIZE)));
+
+        let response = handle(&request(Opcode::NOTIFY, None));
+        assert_eq!(response.edns, None);
+    }
+
+    #[test]
+    fn later_edns_versions_are_answered_with_badvers() {
+        let mut edns = Edns::new(1232);
+        edns.version = 1;
+
+        let response = handle(&request(Opcode::QUERY, Some(edns)));
+
+        assert_eq!(response.header.rescode, ResultCode::BADVERS);
+        assert_eq!(response.edns.map(|edns| edns.version), Some(0));
+        assert!(response.answers.is_empty());
+    }
+}
@@ -0,0 +1,426 @@
+use std::error::Error;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::sync_channel;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::dns::byte_packet_buffer::MAX_MESSAGE_SIZE;
+
+/// The transport a request arrived over, which decides how large the response
+/// to it may be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    /// Responses are limited to 512 bytes, or the payload size the client
+    /// advertised with EDNS.
+    Udp,
+    /// Responses may be as large as the two byte length prefix allows.
+    Tcp,
+}
+
+/// Handles a single request, returning the encoded response to it.
+pub type Handler = fn(&[u8], Transport) -> Result<Vec<u8>, Box<dyn Error>>;
+
+/// The limits the server runs with. Each of them can be set on the command
+/// line, see `ServerConfig::from_args`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerConfig {
+    /// The port to listen on, for both UDP and TCP.
+    pub port: u16,
+    /// The number of UDP queries being answered at once. Once this many are
+    /// in flight, no further queries are read from the socket until one of
+    /// them is done.
+    pub max_in_flight_queries: usize,
+    /// How long a TCP connection may sit idle, waiting for the next query to
+    /// arrive in full, before it is closed. A query sent slowly counts against
+    /// this as a whole, not per read.
+    pub tcp_idle_timeout: Duration,
+    /// The number of TCP connections served at once. Connections made beyond
+    /// this are closed straight away.
+    pub max_tcp_connections: usize,
+}
+
+impl Default for ServerConfig {
+    /// RFC 7766 recommends an idle timeout on the order of seconds, and leaves
+    /// the number of connections to the operator.
+    ///
+    /// https://datatracker.ietf.org/doc/html/rfc7766#section-6.2.3
+    fn default() -> Self {
+        ServerConfig {
+            port: 2053,
+            max_in_flight_queries: 32,
+            tcp_idle_timeout: Duration::from_secs(10),
+            max_tcp_connections: 64,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Take the configuration from command line options, starting from the
+    /// defaults. Apart from the port, none of them may be zero:
+    ///
+    ///     --port <port>
+    ///     --max-in-flight <queries>
+    ///     --max-tcp-connections <connections>
+    ///     --tcp-idle-timeout <seconds>
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<ServerConfig, Box<dyn Error>> {
+        let mut config = ServerConfig::default();
+        let mut args = args.into_iter();
+
+        while let Some(option) = args.next() {
+            let value = args.next().ok_or_else(|| format!("missing value for {}", option))?;
+            let invalid = |_| format!("invalid value for {}: {:?}", option, value);
+
+            match option.as_str() {
+                "--port" => config.port = value.parse().map_err(invalid)?,
+                "--max-in-flight" => config.max_in_flight_queries = value.parse::<NonZeroUsize>().map_err(invalid)?.get(),
+                "--max-tcp-connections" => config.max_tcp_connections = value.parse::<NonZeroUsize>().map_err(invalid)?.get(),
+                "--tcp-idle-timeout" => config.tcp_idle_timeout = Duration::from_secs(value.parse::<NonZeroU64>().map_err(invalid)?.get()),
+                _ => return Err(format!("unknown option {:?}", option).into()),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Receive queries on the socket and answer them on a pool of worker threads,
+/// one per query allowed in flight. Each response is sent back to the address
+/// its query came from.
+pub fn serve_udp(socket: UdpSocket, config: &ServerConfig, handler: Handler) -> io::Result<()> {
+    // Handing over a query blocks until a worker is free to take it, which
+    // stops the socket from being read while every worker is busy.
+    let (sender, receiver) = sync_channel::<(Vec<u8>, SocketAddr)>(0);
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    for _ in 0..config.max_in_flight_queries {
+        let receiver = Arc::clone(&receiver);
+        let socket = socket.try_clone()?;
+
+        thread::spawn(move || loop {
+            // The lock is only held while waiting for the next query, not
+            // while answering it.
+            let query = match receiver.lock() {
+                Ok(receiver) => receiver.recv(),
+                Err(_) => return,
+            };
+
+            let Ok((request, src)) = query else {
+                return;
+            };
+
+            let result = handler(&request, Transport::Udp)
+                .and_then(|response| Ok(socket.send_to(&response, src)?));
+
+            if let Err(e) = result {
+                eprintln!("An error occurred: {}", e);
+            }
+        });
+    }
+
+    // The buffer is sized for the largest possible message, and only the data
+    // actually received is handed to a worker.
+    let mut buffer = [0; MAX_MESSAGE_SIZE];
+
+    loop {
+        // The source is needed in order to send the reply later on.
+        let (size, src) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(e) => {
+                eprintln!("Failed to receive a UDP query: {}", e);
+                continue;
+            }
+        };
+
+        // While every worker is busy, further queries queue up in the receive
+        // buffer of the socket, and are dropped by the system once it is full.
+        if sender.send((buffer[..size].to_vec(), src)).is_err() {
+            return Err(io::Error::other("every UDP worker has stopped"));
+        }
+    }
+}
+
+/// Accept TCP connections on the listener, serving each one on a thread of its
+/// own until it is closed.
+pub fn serve_tcp(listener: TcpListener, config: &ServerConfig, handler: Handler) {
+    let connections = Arc::new(AtomicUsize::new(0));
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Failed to accept a TCP connection: {}", e);
+                continue;
+            }
+        };
+
+        if connections.fetch_add(1, Ordering::SeqCst) >= config.max_tcp_connections {
+            // Dropping the stream closes the connection, which tells the client
+            // to go elsewhere rather than leaving it waiting.
+            connections.fetch_sub(1, Ordering::SeqCst);
+            continue;
+        }
+
+        let connections = Arc::clone(&connections);
+        let idle_timeout = config.tcp_idle_timeout;
+
+        thread::spawn(move || {
+            if let Err(e) = serve_connection(stream, idle_timeout, handler) {
+                eprintln!("An error occurred on a TCP connection: {}", e);
+            }
+
+            connections.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+}
+
+/// RFC 1035
+/// 4.2.2. TCP usage
+///
+/// Messages sent over TCP connections use server port 53 (decimal). The
+/// message is prefixed with a two byte length field which gives the message
+/// length, excluding the two byte length field. This length field allows the
+/// low-level processing to assemble a complete message before beginning to
+/// parse it.
+///
+/// Any number of queries may be sent over the connection, which are answered
+/// in turn until the client closes it or it has been idle for too long.
+fn serve_connection(mut stream: TcpStream, idle_timeout: Duration, handler: Handler) -> Result<(), Box<dyn Error>> {
+    stream.set_write_timeout(Some(idle_timeout))?;
+
+    loop {
+        let request = match read_message(&mut stream, Instant::now() + idle_timeout) {
+            Ok(request) => request,
+            // The client closed the connection, or let it sit idle for too
+            // long, neither of which is an error.
+            Err(e) if matches!(e.kind(), ErrorKind::UnexpectedEof | ErrorKind::TimedOut) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let response = handler(&request, Transport::Tcp)?;
+        write_message(&mut stream, &response)?;
+    }
+}
+
+/// Read a single message from a TCP stream, as prefixed by its length, which
+/// must arrive in full before the deadline. Otherwise, the read fails with a
+/// `TimedOut` error, however steadily the data was trickling in.
+pub fn read_message(stream: &mut TcpStream, deadline: Instant) -> io::Result<Vec<u8>> {
+    let mut length = [0; 2];
+    read_exact_before(stream, &mut length, deadline)?;
+
+    let mut message = vec![0; u16::from_be_bytes(length) as usize];
+    read_exact_before(stream, &mut message, deadline)?;
+
+    Ok(message)
+}
+
+// Fill the buffer from the stream, only ever waiting for the time remaining
+// before the deadline on each read.
+fn read_exact_before(stream: &mut TcpStream, mut buffer: &mut [u8], deadline: Instant) -> io::Result<()> {
+    while !buffer.is_empty() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(ErrorKind::TimedOut, "message did not arrive in time"));
+        }
+
+        stream.set_read_timeout(Some(remaining))?;
+
+        match stream.read(buffer) {
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(size) => buffer = &mut buffer[size..],
+            // Running out of time is reported by the check above, the same
+            // way on every platform.
+            Err(e) if matches!(e.kind(), ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Write a single message to a TCP stream, prefixed by its length. The
+/// message and its length are written at once, as some implementations
+/// expect them to arrive in a single segment.
+pub fn write_message<W: Write>(stream: &mut W, message: &[u8]) -> io::Result<()> {
+    let length = u16::try_from(message.len()).map_err(|_| io::Error::new(ErrorKind::InvalidInput, "message exceeds 65535 bytes"))?;
+
+    let mut data = Vec::with_capacity(message.len() + 2);
+    data.extend_from_slice(&length.to_be_bytes());
+    data.extend_from_slice(message);
+
+    stream.write_all(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream, UdpSocket};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use crate::server::{read_message, serve_connection, serve_udp, write_message, ServerConfig, Transport};
+
+    fn echo(request: &[u8], _: Transport) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(request.to_vec())
+    }
+
+    // Echo the request after a while, so that queries overlap.
+    fn slow_echo(request: &[u8], transport: Transport) -> Result<Vec<u8>, Box<dyn Error>> {
+        thread::sleep(Duration::from_millis(200));
+        echo(request, transport)
+    }
+
+    // Connect to a connection served with the given idle timeout.
+    fn connect(idle_timeout: Duration) -> TcpStream {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve_connection(stream, idle_timeout, echo).unwrap();
+        });
+
+        TcpStream::connect(addr).unwrap()
+    }
+
+    // Wait for the server to close the connection, returning how long it took.
+    fn wait_for_close(stream: &mut TcpStream) -> Duration {
+        let start = Instant::now();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        // The connection may be reset rather than closed, when the client
+        // still had data on the way.
+        match stream.read(&mut [0; 1]) {
+            Ok(size) => assert_eq!(size, 0),
+            Err(e) => assert_ne!(e.kind(), std::io::ErrorKind::WouldBlock),
+        }
+
+        start.elapsed()
+    }
+
+    #[test]
+    fn udp_clients_are_answered_at_once() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        let config = ServerConfig { max_in_flight_queries: 4, ..ServerConfig::default() };
+
+        thread::spawn(move || serve_udp(socket, &config, slow_echo));
+
+        let clients: Vec<_> = (0..8).map(|i| {
+            thread::spawn(move || {
+                let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+                client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+                let query = format!("query {}", i);
+                client.send_to(query.as_bytes(), addr).unwrap();
+
+                let mut buffer = [0; 64];
+                let (size, src) = client.recv_from(&mut buffer).unwrap();
+                assert_eq!(src, addr);
+                assert_eq!(&buffer[..size], query.as_bytes());
+            })
+        }).collect();
+
+        let start = Instant::now();
+        for client in clients {
+            client.join().unwrap();
+        }
+
+        // Eight queries taking 200ms each are answered four at a time.
+        assert!(start.elapsed() < Duration::from_millis(1200));
+    }
+
+    #[test]
+    fn messages_are_answered_in_turn() {
+        let mut stream = connect(Duration::from_secs(5));
+
+        for message in [&b"first"[..], b"second"] {
+            write_message(&mut stream, message).unwrap();
+            assert_eq!(read_message(&mut stream, Instant::now() + Duration::from_secs(5)).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn idle_connections_are_closed() {
+        let mut stream = connect(Duration::from_millis(300));
+
+        assert!(wait_for_close(&mut stream) < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn slow_messages_are_cut_off() {
+        let mut stream = connect(Duration::from_millis(500));
+        let mut writer = stream.try_clone().unwrap();
+
+        // A byte every 100ms never leaves a single read waiting for long, but
+        // the message as a whole takes two seconds to arrive.
+        thread::spawn(move || {
+            for byte in [0, 18].into_iter().chain([b'x'; 18]) {
+                if writer.write_all(&[byte]).is_err() {
+                    return;
+                }
+
+                thread::sleep(Duration::from_millis(100));
+            }
+        });
+
+        assert!(wait_for_close(&mut stream) < Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn reads_stop_at_the_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let start = Instant::now();
+        let error = read_message(&mut stream, start + Duration::from_millis(200)).unwrap_err();
+
+        assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn config_defaults_without_options() {
+        assert_eq!(ServerConfig::from_args(args(&[])).unwrap(), ServerConfig::default());
+    }
+
+    #[test]
+    fn config_is_taken_from_options() {
+        let config = ServerConfig::from_args(args(&[
+            "--port", "53",
+            "--max-in-flight", "128",
+            "--max-tcp-connections", "16",
+            "--tcp-idle-timeout", "30",
+        ])).unwrap();
+
+        assert_eq!(config, ServerConfig {
+            port: 53,
+            max_in_flight_queries: 128,
+            tcp_idle_timeout: Duration::from_secs(30),
+            max_tcp_connections: 16,
+        });
+    }
+
+    #[test]
+    fn invalid_options_are_rejected() {
+        for options in [
+            &["--port"][..],
+            &["--port", "65536"],
+            &["--tcp-idle-timeout", "-1"],
+            &["--tcp-idle-timeout", "0"],
+            &["--max-in-flight", "0"],
+            &["--max-tcp-connections", "0"],
+            &["--verbose", "1"],
+        ] {
+            assert!(ServerConfig::from_args(args(options)).is_err(), "{:?}", options);
+        }
+    }
+}